@@ -0,0 +1,68 @@
+use crate::python;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A reason for a recorded environment to be unusable
+#[derive(Debug)]
+pub enum Problem {
+    /// The directory of the environment was deleted
+    Missing,
+    NoActivate,
+    NoConfig,
+    /// The base interpreter was removed, e.g. by a system Python upgrade
+    NoHome(PathBuf),
+    BrokenPython,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "directory does not exist"),
+            Self::NoActivate => write!(f, "bin/activate does not exist"),
+            Self::NoConfig => write!(f, "pyvenv.cfg has no home interpreter"),
+            Self::NoHome(home) => write!(f, "home interpreter {} does not exist", home.display()),
+            Self::BrokenPython => write!(f, "bin/python does not run"),
+        }
+    }
+}
+
+/// Interpreter the environment was created from, given by `executable` in
+/// `pyvenv.cfg` since Python 3.11, else found in the `home` directory
+fn home_interpreter(env_path: &Path) -> Option<PathBuf> {
+    if let Some(executable) = python::venv_config(env_path, "executable") {
+        return Some(executable.into());
+    }
+    let home = PathBuf::from(python::venv_config(env_path, "home")?);
+    let mut names = vec!["python3".to_string(), "python".to_string()];
+    if let Some(version) = python::venv_version(env_path) {
+        let minor = version.splitn(3, '.').take(2).collect::<Vec<_>>().join(".");
+        names.insert(0, format!("python{}", minor));
+    }
+    let candidates = names.iter().map(|name| home.join(name)).collect::<Vec<_>>();
+    let found = candidates.iter().find(|path| path.is_file()).cloned();
+    Some(found.unwrap_or_else(|| candidates[0].clone()))
+}
+
+/// Check that the environment at the given path can be activated and used,
+/// returning no problem when it is healthy
+pub fn check(env_path: &Path) -> Vec<Problem> {
+    if !env_path.is_dir() {
+        return vec![Problem::Missing];
+    }
+    let mut problems = Vec::new();
+    // Conda environments hold their own interpreter, activated by conda
+    if !python::is_conda(env_path) {
+        if !env_path.join("bin").join("activate").is_file() {
+            problems.push(Problem::NoActivate);
+        }
+        match home_interpreter(env_path) {
+            Some(home) if !home.is_file() => problems.push(Problem::NoHome(home)),
+            Some(_) => {}
+            None => problems.push(Problem::NoConfig),
+        }
+    }
+    if python::version(&env_path.join("bin").join("python")).is_none() {
+        problems.push(Problem::BrokenPython);
+    }
+    problems
+}
@@ -0,0 +1,153 @@
+use crate::health;
+use crate::python;
+use crate::registry::Environment;
+use chrono::{DateTime, Local};
+use colored::Colorize;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What `pem list` shows of a recorded environment
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    name: String,
+    path: PathBuf,
+    python: Option<String>,
+    /// Disk usage in bytes
+    size: u64,
+    /// Number of installed distributions
+    packages: usize,
+    last_used: Option<DateTime<Local>>,
+    /// Reasons for the environment to be broken, empty when healthy
+    problems: Vec<String>,
+}
+
+/// Total size of the files below a directory, without following links
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| disk_usage(&entry.path()))
+        .sum()
+}
+
+/// Number of `*.dist-info` directories in `site-packages`
+fn count_packages(env_path: &Path) -> usize {
+    let Some(entries) = python::site_packages(env_path).and_then(|path| fs::read_dir(path).ok())
+    else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".dist-info"))
+        .count()
+}
+
+/// Size like "1.5M", in powers of 1024
+fn human_size(size: u64) -> String {
+    let mut value = size as f64;
+    for unit in ["B", "K", "M", "G"] {
+        if value < 1024.0 {
+            return match unit {
+                "B" => format!("{}{}", size, unit),
+                _ => format!("{:.1}{}", value, unit),
+            };
+        }
+        value /= 1024.0;
+    }
+    format!("{:.1}T", value)
+}
+
+impl Summary {
+    pub fn new(name: &str, env: &Environment) -> Self {
+        Self {
+            name: name.to_string(),
+            path: env.path.clone(),
+            python: python::venv_version(&env.path).or_else(|| env.python.clone()),
+            size: disk_usage(&env.path),
+            packages: count_packages(&env.path),
+            last_used: env.last_used,
+            problems: health::check(&env.path)
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Print the summaries as a table with aligned columns
+pub fn print_table(summaries: &[Summary]) {
+    let rows = summaries
+        .iter()
+        .map(|summary| {
+            [
+                summary.name.clone(),
+                summary.python.clone().unwrap_or("-".to_string()),
+                human_size(summary.size),
+                summary.packages.to_string(),
+                match summary.last_used {
+                    Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+                    None => "never".to_string(),
+                },
+                if summary.problems.is_empty() {
+                    "ok".to_string()
+                } else {
+                    "broken".to_string()
+                },
+                summary.path.display().to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        "NAME",
+        "PYTHON",
+        "SIZE",
+        "PACKAGES",
+        "LAST USED",
+        "STATUS",
+        "PATH",
+    ];
+    let mut widths = header.map(|title| title.len());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = header
+        .iter()
+        .zip(widths.iter())
+        .map(|(title, width)| format!("{:<width$}", title, width = width))
+        .collect::<Vec<_>>()
+        .join("  ");
+    println!("{}", line.trim_end().bold());
+    for row in rows.iter() {
+        let cells = row
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                let cell = match i {
+                    // Numbers are aligned to the right
+                    2 | 3 => format!("{:>width$}", cell, width = width),
+                    6 => cell.clone(),
+                    _ => format!("{:<width$}", cell, width = width),
+                };
+                match (i, row[5].as_str()) {
+                    (0, _) => cell.bold().to_string(),
+                    (5, "ok") => cell.green().to_string(),
+                    (5, _) => cell.red().to_string(),
+                    _ => cell,
+                }
+            })
+            .collect::<Vec<_>>();
+        println!("{}", cells.join("  "));
+    }
+}
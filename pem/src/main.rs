@@ -1,8 +1,21 @@
+mod health;
+mod list;
+mod packages;
+mod project;
+mod python;
+mod registry;
+mod scan;
+mod shell;
+
 use anyhow::{Result, anyhow};
-use clap::{Parser, Subcommand};
+use chrono::Local;
+use clap::{Args, Parser, Subcommand};
 use colored::Colorize;
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
+use registry::{Environment, Registry, Store};
+use shell::Shell;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::process::CommandExt;
 use std::{
     env, fs,
     path::{Path, PathBuf},
@@ -20,112 +33,544 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Print the list of all recorded Python virtual environments
-    List,
+    List {
+        /// Print the list as JSON
+        #[arg(long)]
+        json: bool,
+    },
     /// Add Python virtual environment with given name and path
-    Add { name: String, path: PathBuf },
+    Add {
+        name: String,
+        path: PathBuf,
+        #[command(flatten)]
+        metadata: Metadata,
+    },
+    /// Create a Python virtual environment with given name and add it
+    Create {
+        name: String,
+        /// Python version looked up on PATH, like 3.12, or path to an interpreter
+        #[arg(long)]
+        python: Option<String>,
+        /// Location of the environment [default: ~/.local/pem/venvs/NAME]
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Requirements file installed with pip
+        #[arg(long, short)]
+        requirements: Option<PathBuf>,
+        #[command(flatten)]
+        metadata: Metadata,
+    },
     /// Remove Python virtual environment with given name
     Remove { name: String },
     /// Modify path of Python virtual environment with given name
     Amend { name: String, path: PathBuf },
     /// Get path to activate script of Python virtual environment with given name
     Get { name: String },
+    /// Run a command in Python virtual environment with given name, without activating it
+    Run {
+        name: String,
+        /// Command and its arguments, after "--"
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Find Python virtual environments below directories and add them, asking for each one
+    Scan {
+        /// Directories to scan [default: home directory]
+        dirs: Vec<PathBuf>,
+        /// Maximum depth of the environments below the directories
+        #[arg(long, default_value_t = 5)]
+        max_depth: usize,
+        /// Add without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// List the packages installed in Python virtual environment with given name
+    Packages { name: String },
+    /// Print the packages of Python virtual environment with given name as requirements
+    Freeze {
+        name: String,
+        /// Include pip, setuptools and wheel
+        #[arg(long)]
+        all: bool,
+    },
+    /// Compare the packages of two Python virtual environments
+    Diff { a: String, b: String },
+    /// Check that all recorded Python virtual environments are usable
+    Doctor,
+    /// Remove broken Python virtual environments from the record, asking for each one
+    Prune {
+        /// Remove without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Select the Python virtual environment of the project in the current directory
+    Use { name: String },
+    /// Print the Python virtual environment selected for the current directory
+    Current,
+    /// Print the shell function enabling `pem activate`, `pem deactivate` and
+    /// the activation of project environments when changing directory
+    Init { shell: Shell },
+    /// Print the commands switching to the environment of the current
+    /// directory, run by the shell function of `pem init`
+    #[command(hide = true)]
+    Hook {
+        #[arg(long, value_enum, default_value_t = Shell::Bash)]
+        shell: Shell,
+    },
+    /// Activate Python virtual environment with given name in the current shell
+    Activate {
+        name: String,
+        /// Shell reading the output, set by the function of `pem init`
+        #[arg(long, value_enum, default_value_t = Shell::Bash)]
+        shell: Shell,
+    },
+    /// Deactivate the active Python virtual environment in the current shell
+    Deactivate {
+        /// Shell reading the output, set by the function of `pem init`
+        #[arg(long, value_enum, default_value_t = Shell::Bash)]
+        shell: Shell,
+    },
+}
+
+/// Metadata given when recording an environment
+#[derive(Args, Debug)]
+struct Metadata {
+    /// Description of the environment
+    #[arg(long, short)]
+    description: Option<String>,
+    /// Tag of the environment (may be repeated)
+    #[arg(long = "tag", short)]
+    tags: Vec<String>,
 }
 
-fn load_envs(fpath: &Path, record: &mut HashMap<String, PathBuf>) -> Result<()> {
-    let file = fs::File::open(fpath)?;
-    let reader = BufReader::new(file);
-    for line in reader.lines() {
-        let line = line?;
-        let (name, path_str) = line.split_once(" ").unwrap();
-        let mut path = PathBuf::new();
-        path.set_file_name(path_str.trim());
-        record.insert(name.trim().to_string(), path);
+impl Metadata {
+    fn apply(&self, env: Environment) -> Environment {
+        Environment {
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+            ..env
+        }
     }
-    Ok(())
 }
 
-fn save_envs(fpath: &Path, record: &HashMap<String, PathBuf>) -> Result<()> {
-    let mut file = fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(fpath)?;
-    for (name, path) in record.iter() {
-        writeln!(file, "{} {}", name, path.display())?;
+/// The output of `pem activate` and `pem deactivate` is only meant to be
+/// evaluated by the shell function of `pem init`
+fn check_shell_integration() -> Result<()> {
+    if io::stdout().is_terminal() {
+        return Err(anyhow!(
+            "Shell integration is not set up, add 'eval \"$(pem init bash)\"' to ~/.bashrc, \
+            'eval \"$(pem init zsh)\"' to ~/.zshrc or 'pem init fish | source' to \
+            ~/.config/fish/config.fish"
+        ));
     }
     Ok(())
 }
 
-fn set_environment(
-    fpath: &Path,
-    record: &mut HashMap<String, PathBuf>,
-    env_name: &String,
-    env_path: &PathBuf,
-) -> Result<()> {
-    let path = Path::new(env_path).join("bin").join("activate");
-    if !path.is_file() {
+/// Check that a directory holds a Python virtual environment, returning
+/// its absolute path
+fn check_environment(env_path: &Path) -> Result<PathBuf> {
+    let path = env_path.join("bin").join("activate");
+    if !path.is_file() && !python::is_conda(env_path) {
         return Err(anyhow!("Not a valid python environment"));
     }
-    record.insert(env_name.to_string(), env_path.to_path_buf());
-    save_envs(fpath, record)?;
+    Ok(env_path.canonicalize()?)
+}
+
+fn not_found(name: &str) -> anyhow::Error {
+    anyhow!("No environment with name '{}' found", name)
+}
+
+fn add_environment(registry: &mut Registry, name: &str, env: Environment) -> Result<()> {
+    if registry.envs.contains_key(name) {
+        let msg = format!("Environment with name '{}' already added", name);
+        return Err(anyhow!(msg));
+    }
+    registry.envs.insert(name.to_string(), env);
     Ok(())
 }
 
+/// The environment selected by the closest `.pem` file, if any
+fn project_environment(store: &Store) -> Result<Option<(String, Environment)>> {
+    let Some(file) = project::find(&env::current_dir()?) else {
+        return Ok(None);
+    };
+    let name = project::read(&file)?;
+    match store.load()?.envs.remove(&name) {
+        Some(env) => Ok(Some((name, env))),
+        None => Err(anyhow!("{}: {}", file.display(), not_found(&name))),
+    }
+}
+
+/// Commands activating the environment of the current project, and
+/// deactivating it when leaving the project. Environments activated with
+/// `pem activate` are left alone, the others are marked with `PEM_AUTO`.
+fn hook_script(store: &Store, shell: Shell) -> Result<String> {
+    let active = env::var("PEM_ENV").ok();
+    let auto = env::var_os("PEM_AUTO").is_some();
+    if active.is_some() && !auto {
+        return Ok(String::new());
+    }
+    let mut script = String::new();
+    match project_environment(store)? {
+        Some((name, _)) if active.as_ref() == Some(&name) => {}
+        Some((name, env)) => {
+            if active.is_some() && env::var_os("VIRTUAL_ENV").is_some() {
+                script += &shell.deactivate_script();
+            }
+            script += &shell.activate_script(&name, &env.path)?;
+            script += &shell.export("PEM_AUTO", "1");
+        }
+        None if active.is_some() => {
+            // The environment may have been left by hand, with only the variables remaining
+            if env::var_os("VIRTUAL_ENV").is_some() {
+                script += &shell.deactivate_script();
+            } else {
+                script += &shell.unset("PEM_ENV");
+                script += &shell.unset("PEM_AUTO");
+            }
+        }
+        None => {}
+    }
+    Ok(script)
+}
+
+/// Packages installed in the recorded environment with given name
+fn installed_packages(registry: &Registry, name: &str) -> Result<Vec<packages::Package>> {
+    let env = registry.envs.get(name).ok_or_else(|| not_found(name))?;
+    packages::installed(&env.path)
+}
+
+/// Ask a yes or no question on the terminal, defaulting to no
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
     let home_dir = env::home_dir().expect("Could not find home directory");
     let pem_dir = home_dir.join(".local").join("pem");
-    if !pem_dir.is_dir() {
-        fs::create_dir_all(&pem_dir)?;
-    }
-    let pem_file = pem_dir.join("envs");
-    let mut record: HashMap<String, PathBuf> = HashMap::new();
-    if pem_file.is_file() {
-        load_envs(pem_file.as_path(), &mut record)?;
-    }
+    let store = Store::new(pem_dir.clone());
     match &cli.command {
-        Commands::List => {
-            if record.is_empty() {
+        Commands::List { json } => {
+            let registry = store.load()?;
+            let summaries = registry
+                .envs
+                .iter()
+                .map(|(name, env)| list::Summary::new(name, env))
+                .collect::<Vec<_>>();
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&summaries)?);
+            } else if summaries.is_empty() {
                 println!("No virtual environment recorded");
-                return Ok(());
-            }
-            for (name, path) in record.iter() {
-                println!("{:<10} {}", name.bold(), path.display());
+            } else {
+                list::print_table(&summaries);
             }
         }
         Commands::Amend { name, path } => {
-            if !record.contains_key(name) {
-                let msg = format!("No environment with name '{}' found", name);
-                return Err(anyhow!(msg));
-            }
-            set_environment(pem_file.as_path(), &mut record, name, path)?;
+            let path = check_environment(path)?;
+            store.update(|registry| {
+                let env = registry.envs.get_mut(name).ok_or_else(|| not_found(name))?;
+                env.python = python::venv_version(&path);
+                env.path = path;
+                Ok(())
+            })?;
+        }
+        Commands::Add {
+            name,
+            path,
+            metadata,
+        } => {
+            let env = metadata.apply(Environment::new(check_environment(path)?));
+            store.update(|registry| add_environment(registry, name, env))?;
         }
-        Commands::Add { name, path } => {
-            if record.contains_key(name) {
+        Commands::Create {
+            name,
+            python,
+            path,
+            requirements,
+            metadata,
+        } => {
+            if store.load()?.envs.contains_key(name) {
                 let msg = format!("Environment with name '{}' already added", name);
                 return Err(anyhow!(msg));
             }
-            set_environment(pem_file.as_path(), &mut record, name, path)?;
+            let env_path = match path {
+                Some(path) => path.clone(),
+                None => pem_dir.join("venvs").join(name),
+            };
+            if env_path.exists() {
+                return Err(anyhow!("{} already exists", env_path.display()));
+            }
+            let interpreter = python::select_interpreter(python.as_deref())?;
+            println!(
+                "Creating {} with Python {} ({})",
+                name.bold(),
+                interpreter.version,
+                interpreter.path.display()
+            );
+            // Do not leave a half-built environment behind
+            if let Err(err) = python::create_venv(&interpreter, &env_path, requirements.as_deref())
+            {
+                if env_path.exists() {
+                    fs::remove_dir_all(&env_path)?;
+                }
+                return Err(err);
+            }
+            let env = metadata.apply(Environment::new(env_path.canonicalize()?));
+            store.update(|registry| add_environment(registry, name, env))?;
         }
-        Commands::Remove { name } => match record.remove(name) {
-            Some(_) => {
-                save_envs(pem_file.as_path(), &record)?;
+        Commands::Remove { name } => {
+            store.update(|registry| match registry.envs.remove(name) {
+                Some(_) => Ok(()),
+                None => Err(not_found(name)),
+            })?;
+        }
+        Commands::Get { name } => match store.load()?.envs.get(name) {
+            Some(env) => {
+                let activate_path = env.path.join("bin").join("activate");
+                println!("{}", activate_path.display());
             }
             None => {
-                let msg = format!("No environment with name '{}' found", name);
+                let msg = format!("Environment {} is not known", name);
                 return Err(anyhow!(msg));
             }
         },
-        Commands::Get { name } => match record.get(name) {
-            Some(path) => {
-                let activate_path = path.join("bin").join("activate");
-                println!("{}", activate_path.display());
+        Commands::Run { name, command } => {
+            let env_path = match store.load()?.envs.get(name) {
+                Some(env) => env.path.clone(),
+                None => return Err(not_found(name)),
+            };
+            // Recording the use is best effort, and skipped in SLURM jobs whose
+            // tasks would all contend for the lock of the home directory
+            if env::var_os("SLURM_JOB_ID").is_none() {
+                let _ = store.try_update(|registry| {
+                    if let Some(env) = registry.envs.get_mut(name) {
+                        env.last_used = Some(Local::now());
+                    }
+                });
+            }
+            // Replace the process, so that the exit code and signals reach the caller
+            let err = python::venv_command(&env_path, &command[0])?
+                .args(&command[1..])
+                .exec();
+            return Err(anyhow!("Could not run {}: {}", command[0], err));
+        }
+        Commands::Scan {
+            dirs,
+            max_depth,
+            yes,
+        } => {
+            if !yes && !io::stdin().is_terminal() {
+                return Err(anyhow!("Not running in a terminal, use --yes to add"));
+            }
+            let dirs = if dirs.is_empty() {
+                vec![home_dir.clone()]
+            } else {
+                dirs.clone()
+            };
+            let mut found = Vec::new();
+            for dir in dirs.iter() {
+                scan::find_environments(dir, *max_depth, &mut found);
+            }
+            let registry = store.load()?;
+            let mut known = registry
+                .envs
+                .values()
+                .map(|env| env.path.clone())
+                .collect::<HashSet<_>>();
+            let mut taken = registry.envs.keys().cloned().collect::<HashSet<_>>();
+            let mut added = Vec::new();
+            for path in found {
+                let Ok(path) = path.canonicalize() else {
+                    continue;
+                };
+                if !known.insert(path.clone()) {
+                    continue;
+                }
+                let name = scan::propose_name(&path, &taken);
+                let question = format!("Add {} as {}?", path.display(), name.bold());
+                if *yes || confirm(&question)? {
+                    taken.insert(name.clone());
+                    added.push((name, Environment::new(path)));
+                }
+            }
+            store.update(|registry| {
+                for (name, env) in added.iter() {
+                    add_environment(registry, name, env.clone())?;
+                }
+                Ok(())
+            })?;
+            if added.is_empty() {
+                println!("No environment added");
+            } else {
+                println!("Added {} environments", added.len());
             }
+        }
+        Commands::Packages { name } => {
+            let packages = installed_packages(&store.load()?, name)?;
+            let width = packages.iter().map(|p| p.name.len()).max().unwrap_or(0);
+            for package in packages.iter() {
+                println!(
+                    "{:<width$}  {}",
+                    package.name,
+                    package.version,
+                    width = width
+                );
+            }
+        }
+        Commands::Freeze { name, all } => {
+            // Left out by pip freeze, as they come with the environment
+            let skipped = ["pip", "setuptools", "wheel", "distribute"];
+            for package in installed_packages(&store.load()?, name)? {
+                if *all || !skipped.contains(&package.key().as_str()) {
+                    println!("{}=={}", package.name, package.version);
+                }
+            }
+        }
+        Commands::Diff { a, b } => {
+            let registry = store.load()?;
+            let a_packages = installed_packages(&registry, a)?;
+            let b_packages = installed_packages(&registry, b)?;
+            let a_versions = a_packages
+                .iter()
+                .map(|package| (package.key(), package))
+                .collect::<BTreeMap<_, _>>();
+            let b_versions = b_packages
+                .iter()
+                .map(|package| (package.key(), package))
+                .collect::<BTreeMap<_, _>>();
+            let keys = a_versions
+                .keys()
+                .chain(b_versions.keys())
+                .collect::<BTreeSet<_>>();
+            let mut same = true;
+            for key in keys {
+                match (a_versions.get(key), b_versions.get(key)) {
+                    (Some(p), None) => println!("{}", format!("- {} {}", p.name, p.version).red()),
+                    (None, Some(p)) => {
+                        println!("{}", format!("+ {} {}", p.name, p.version).green())
+                    }
+                    (Some(p), Some(q)) if p.version != q.version => println!(
+                        "{}",
+                        format!("~ {} {} -> {}", p.name, p.version, q.version).yellow()
+                    ),
+                    _ => continue,
+                }
+                same = false;
+            }
+            if same {
+                println!("{} and {} have the same packages", a.bold(), b.bold());
+            }
+        }
+        Commands::Doctor => {
+            let registry = store.load()?;
+            let mut broken = 0;
+            for (name, env) in registry.envs.iter() {
+                let problems = health::check(&env.path);
+                if problems.is_empty() {
+                    println!("{:<10} {}", name.bold(), "ok".green());
+                    continue;
+                }
+                broken += 1;
+                println!("{:<10} {}", name.bold(), "broken".red());
+                for problem in problems.iter() {
+                    println!("    {}", problem);
+                }
+            }
+            if broken > 0 {
+                return Err(anyhow!(
+                    "{} of {} environments are broken, remove them with 'pem prune'",
+                    broken,
+                    registry.envs.len()
+                ));
+            }
+        }
+        Commands::Prune { yes } => {
+            if !yes && !io::stdin().is_terminal() {
+                return Err(anyhow!("Not running in a terminal, use --yes to prune"));
+            }
+            let registry = store.load()?;
+            let mut pruned = Vec::new();
+            for (name, env) in registry.envs.iter() {
+                let problems = health::check(&env.path);
+                if problems.is_empty() {
+                    continue;
+                }
+                let reasons = problems
+                    .iter()
+                    .map(|problem| problem.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if *yes || confirm(&format!("Remove {} ({})?", name.bold(), reasons))? {
+                    pruned.push((name, &env.path));
+                }
+            }
+            // Only the records are removed, what is left of the directories is kept
+            store.update(|registry| {
+                for (name, path) in pruned.iter() {
+                    // Skip environments amended since they were checked
+                    if registry
+                        .envs
+                        .get(*name)
+                        .is_some_and(|env| &env.path == *path)
+                    {
+                        registry.envs.remove(*name);
+                    }
+                }
+                Ok(())
+            })?;
+            if pruned.is_empty() {
+                println!("No environment removed");
+            } else {
+                println!("Removed {} environments", pruned.len());
+            }
+        }
+        Commands::Use { name } => {
+            if !store.load()?.envs.contains_key(name) {
+                return Err(not_found(name));
+            }
+            project::write(&env::current_dir()?, name)?;
+        }
+        Commands::Current => match project_environment(&store)? {
+            Some((name, env)) => println!("{:<10} {}", name.bold(), env.path.display()),
             None => {
-                let msg = format!("Environment {} is not known", name);
-                return Err(anyhow!(msg));
+                return Err(anyhow!(
+                    "No {} file found in the current directory or its parents",
+                    project::PROJECT_FILE
+                ));
             }
         },
+        Commands::Init { shell } => {
+            print!("{}", shell.init_script());
+        }
+        Commands::Hook { shell } => {
+            print!("{}", hook_script(&store, *shell)?);
+        }
+        Commands::Activate { name, shell } => {
+            check_shell_integration()?;
+            let script = match store.load()?.envs.get(name) {
+                Some(env) => shell.activate_script(name, &env.path)?,
+                None => return Err(anyhow!("Environment {} is not known", name)),
+            };
+            // Like for run, activation must not wait for the lock to record the use
+            let _ = store.try_update(|registry| {
+                if let Some(env) = registry.envs.get_mut(name) {
+                    env.last_used = Some(Local::now());
+                }
+            });
+            print!("{}", script);
+        }
+        Commands::Deactivate { shell } => {
+            check_shell_integration()?;
+            if env::var_os("VIRTUAL_ENV").is_none() {
+                return Err(anyhow!("No virtual environment is active"));
+            }
+            print!("{}", shell.deactivate_script());
+        }
     }
     Ok(())
 }
@@ -0,0 +1,71 @@
+use crate::python;
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::Path;
+
+/// A distribution installed in `site-packages`
+#[derive(Clone, Debug)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+}
+
+impl Package {
+    /// Name compared between environments, as normalized by pip
+    pub fn key(&self) -> String {
+        let mut key = String::new();
+        for c in self.name.chars() {
+            match c {
+                '-' | '_' | '.' if key.ends_with('-') => {}
+                '-' | '_' | '.' => key.push('-'),
+                _ => key.push(c.to_ascii_lowercase()),
+            }
+        }
+        key
+    }
+}
+
+/// Read the `Name` and `Version` headers of a `METADATA` or `PKG-INFO` file
+fn read_metadata(path: &Path) -> Option<Package> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut name = None;
+    let mut version = None;
+    // The headers end at the first blank line, followed by the description
+    for line in content.lines().take_while(|line| !line.is_empty()) {
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Version:") {
+            version = Some(value.trim().to_string());
+        }
+    }
+    Some(Package {
+        name: name?,
+        version: version?,
+    })
+}
+
+/// Distributions installed in an environment, sorted by name, read from the
+/// `*.dist-info` and `*.egg-info` of `site-packages` without running Python
+pub fn installed(env_path: &Path) -> Result<Vec<Package>> {
+    let site_packages = python::site_packages(env_path)
+        .ok_or_else(|| anyhow!("No site-packages found in {}", env_path.display()))?;
+    let mut packages = Vec::new();
+    for entry in fs::read_dir(&site_packages)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let metadata = if name.ends_with(".dist-info") {
+            path.join("METADATA")
+        } else if name.ends_with(".egg-info") && path.is_dir() {
+            path.join("PKG-INFO")
+        } else if name.ends_with(".egg-info") {
+            path.clone()
+        } else {
+            continue;
+        };
+        if let Some(package) = read_metadata(&metadata) {
+            packages.push(package);
+        }
+    }
+    packages.sort_by_key(|package| package.key());
+    Ok(packages)
+}
@@ -0,0 +1,31 @@
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File naming the environment of a project, in its root directory
+pub const PROJECT_FILE: &str = ".pem";
+
+/// The closest `.pem` file in the directory or its parents
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Name of the environment given in a `.pem` file
+pub fn read(path: &Path) -> Result<String> {
+    let content = fs::read_to_string(path)?;
+    let name = content.trim();
+    if name.is_empty() || name.contains('\n') {
+        return Err(anyhow!(
+            "{}: expected a single environment name",
+            path.display()
+        ));
+    }
+    Ok(name.to_string())
+}
+
+pub fn write(dir: &Path, name: &str) -> Result<()> {
+    fs::write(dir.join(PROJECT_FILE), format!("{}\n", name))?;
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A Python interpreter found on `PATH`
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+    pub path: PathBuf,
+    pub version: String,
+}
+
+/// Names like `python`, `python3` or `python3.12`
+fn is_python_name(name: &str) -> bool {
+    let Some(rest) = name.strip_prefix("python") else {
+        return false;
+    };
+    rest.is_empty()
+        || rest
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Version printed by `python --version`, like "3.12.4"
+pub fn version(path: &Path) -> Option<String> {
+    let output = Command::new(path).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // Python 2 prints its version on stderr
+    let text = [output.stdout, output.stderr].concat();
+    let text = String::from_utf8_lossy(&text);
+    text.trim().strip_prefix("Python ").map(|v| v.to_string())
+}
+
+/// Interpreters on `PATH`, in order of precedence, each one listed once
+/// even when reachable through several names
+pub fn find_interpreters() -> Vec<Interpreter> {
+    let mut result = Vec::new();
+    let mut seen = HashSet::new();
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    for dir in env::split_paths(&path_var) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut names = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| is_python_name(name))
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let path = dir.join(name);
+            if !is_executable(&path) {
+                continue;
+            }
+            let Ok(resolved) = fs::canonicalize(&path) else {
+                continue;
+            };
+            if !seen.insert(resolved) {
+                continue;
+            }
+            if let Some(version) = version(&path) {
+                result.push(Interpreter { path, version });
+            }
+        }
+    }
+    result
+}
+
+/// Whether "3.12.4" matches a requested version like "3", "3.12" or "3.12.4"
+fn matches_version(version: &str, requested: &str) -> bool {
+    version == requested || version.starts_with(&format!("{}.", requested))
+}
+
+/// Select the first interpreter on `PATH` with the requested version,
+/// Python 3 by default, or the interpreter at the given path
+pub fn select_interpreter(requested: Option<&str>) -> Result<Interpreter> {
+    if let Some(requested) = requested
+        && requested.contains('/')
+    {
+        let path = PathBuf::from(requested);
+        let version =
+            version(&path).ok_or_else(|| anyhow!("{} is not a Python interpreter", requested))?;
+        return Ok(Interpreter { path, version });
+    }
+    let requested = requested.unwrap_or("3");
+    let interpreters = find_interpreters();
+    if let Some(interpreter) = interpreters
+        .iter()
+        .find(|interpreter| matches_version(&interpreter.version, requested))
+    {
+        return Ok(interpreter.clone());
+    }
+    let available = interpreters
+        .iter()
+        .map(|interpreter| format!("{} ({})", interpreter.version, interpreter.path.display()))
+        .collect::<Vec<_>>();
+    let msg = if available.is_empty() {
+        format!("No Python {} found on PATH", requested)
+    } else {
+        format!(
+            "No Python {} found on PATH, available: {}",
+            requested,
+            available.join(", ")
+        )
+    };
+    Err(anyhow!(msg))
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let status = command.status()?;
+    if !status.success() {
+        return Err(anyhow!("{:?} failed with {}", command, status));
+    }
+    Ok(())
+}
+
+/// Run `python -m venv`, then install the requirements if any
+pub fn create_venv(
+    interpreter: &Interpreter,
+    path: &Path,
+    requirements: Option<&Path>,
+) -> Result<()> {
+    run(Command::new(&interpreter.path)
+        .args(["-m", "venv"])
+        .arg(path))?;
+    if let Some(requirements) = requirements {
+        run(Command::new(path.join("bin").join("python"))
+            .args(["-m", "pip", "install", "-r"])
+            .arg(requirements))?;
+    }
+    Ok(())
+}
+
+/// Value of a key in the `pyvenv.cfg` of a virtual environment
+pub fn venv_config(env_path: &Path, key: &str) -> Option<String> {
+    let content = fs::read_to_string(env_path.join("pyvenv.cfg")).ok()?;
+    content.lines().find_map(|line| {
+        let (k, value) = line.split_once('=')?;
+        (k.trim() == key).then(|| value.trim().to_string())
+    })
+}
+
+/// A virtual environment made by `python -m venv`, virtualenv or uv
+pub fn is_venv(path: &Path) -> bool {
+    path.join("pyvenv.cfg").is_file() && path.join("bin").join("activate").is_file()
+}
+
+/// A conda environment, which has no `pyvenv.cfg` nor activate script
+pub fn is_conda(path: &Path) -> bool {
+    path.join("conda-meta").is_dir()
+}
+
+/// Python version of a conda environment, from its package record like
+/// `conda-meta/python-3.12.4-h5148396_1.json`
+fn conda_version(env_path: &Path) -> Option<String> {
+    let entries = fs::read_dir(env_path.join("conda-meta")).ok()?;
+    entries.filter_map(|entry| entry.ok()).find_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let rest = name.strip_prefix("python-")?.strip_suffix(".json")?;
+        let (version, _) = rest.split_once('-')?;
+        version
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then(|| version.to_string())
+    })
+}
+
+/// Python version of a virtual environment, read from its `pyvenv.cfg`
+pub fn venv_version(env_path: &Path) -> Option<String> {
+    if is_conda(env_path) {
+        return conda_version(env_path);
+    }
+    if let Some(version) = venv_config(env_path, "version") {
+        return Some(version);
+    }
+    // Written by uv, like "3.12.4.final.0"
+    let version = venv_config(env_path, "version_info")?;
+    Some(version.splitn(4, '.').take(3).collect::<Vec<_>>().join("."))
+}
+
+/// The `site-packages` directory of a virtual environment, like
+/// `lib/python3.12/site-packages`
+pub fn site_packages(env_path: &Path) -> Option<PathBuf> {
+    let entries = fs::read_dir(env_path.join("lib")).ok()?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| is_python_name(&entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path().join("site-packages"))
+        .find(|path| path.is_dir())
+}
+
+/// Command running a program of the virtual environment or of `PATH`, as it
+/// would in the activated environment
+pub fn venv_command(env_path: &Path, program: &str) -> Result<Command> {
+    let bin = env_path.join("bin");
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    let paths = std::iter::once(bin).chain(env::split_paths(&path_var));
+    let mut command = Command::new(program);
+    command
+        .env("PATH", env::join_paths(paths)?)
+        .env("VIRTUAL_ENV", env_path)
+        .env_remove("PYTHONHOME");
+    Ok(command)
+}
@@ -0,0 +1,249 @@
+use crate::python;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+// Version of the registry format written by this pem
+const VERSION: u32 = 1;
+
+/// A recorded Python virtual environment
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Environment {
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Last time the environment was activated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<DateTime<Local>>,
+}
+
+impl Environment {
+    /// Record an environment, reading its Python version from `pyvenv.cfg`
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            python: python::venv_version(&path),
+            path,
+            created: Some(Local::now()),
+            description: None,
+            tags: Vec::new(),
+            last_used: None,
+        }
+    }
+}
+
+/// Content of the registry file, with environments sorted by name
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Registry {
+    version: u32,
+    #[serde(default)]
+    pub envs: BTreeMap<String, Environment>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            envs: BTreeMap::new(),
+        }
+    }
+}
+
+/// Location of the registry in the pem directory, `~/.local/pem`
+pub struct Store {
+    dir: PathBuf,
+}
+
+/// Parse the `envs` file of older versions, made of "name path" lines
+fn parse_legacy(path: &Path) -> Result<Registry> {
+    let mut registry = Registry::default();
+    let reader = BufReader::new(File::open(path)?);
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((name, env_path)) = line.split_once(char::is_whitespace) else {
+            return Err(anyhow!("{}: invalid line '{}'", path.display(), line));
+        };
+        let env_path = PathBuf::from(env_path.trim());
+        let env = Environment {
+            created: None,
+            ..Environment::new(env_path)
+        };
+        registry.envs.insert(name.to_string(), env);
+    }
+    Ok(registry)
+}
+
+impl Store {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join("registry.toml")
+    }
+
+    fn legacy_path(&self) -> PathBuf {
+        self.dir.join("envs")
+    }
+
+    /// Lock a separate file, as the registry itself is replaced on each write
+    fn lock(&self) -> Result<File> {
+        fs::create_dir_all(&self.dir)?;
+        let file = File::create(self.dir.join("registry.lock"))?;
+        file.lock_exclusive()?;
+        Ok(file)
+    }
+
+    fn try_lock(&self) -> Result<File> {
+        let file = File::create(self.dir.join("registry.lock"))?;
+        file.try_lock_exclusive()?;
+        Ok(file)
+    }
+
+    fn read(&self) -> Result<Registry> {
+        let path = self.path();
+        if !path.is_file() {
+            return Ok(Registry::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        let registry: Registry =
+            toml::from_str(&content).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        if registry.version > VERSION {
+            return Err(anyhow!(
+                "{}: version {} is not supported by this pem, upgrade it",
+                path.display(),
+                registry.version
+            ));
+        }
+        Ok(registry)
+    }
+
+    /// Write to a temporary file renamed over the registry, so that readers
+    /// never see a partial file
+    fn write(&self, registry: &Registry) -> Result<()> {
+        let path = self.path();
+        let temp_path = self.dir.join("registry.toml.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(toml::to_string_pretty(registry)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// Import the `envs` file of older versions, which is kept as `envs.old`
+    fn migrate(&self) -> Result<()> {
+        let legacy = self.legacy_path();
+        if self.path().is_file() || !legacy.is_file() {
+            return Ok(());
+        }
+        let registry = parse_legacy(&legacy)?;
+        self.write(&registry)?;
+        fs::rename(&legacy, self.dir.join("envs.old"))?;
+        eprintln!(
+            "Migrated {} environments from {} to {}",
+            registry.envs.len(),
+            legacy.display(),
+            self.path().display()
+        );
+        Ok(())
+    }
+
+    /// Read the registry, migrating the old format first if needed. Writes
+    /// replace the file at once, so reading needs no lock and works on a
+    /// read-only home directory or one without flock support.
+    pub fn load(&self) -> Result<Registry> {
+        if !self.path().is_file() && self.legacy_path().is_file() {
+            let _lock = self.lock()?;
+            self.migrate()?;
+        }
+        self.read()
+    }
+
+    /// Modify the registry while holding an exclusive lock, so that
+    /// concurrent shells do not lose each other's changes
+    pub fn update<T>(&self, change: impl FnOnce(&mut Registry) -> Result<T>) -> Result<T> {
+        let _lock = self.lock()?;
+        self.update_locked(change)
+    }
+
+    /// Modify the registry only if it can be locked right away, for changes
+    /// which are not worth waiting for
+    pub fn try_update(&self, change: impl FnOnce(&mut Registry)) -> Result<()> {
+        let _lock = self.try_lock()?;
+        self.update_locked(|registry| {
+            change(registry);
+            Ok(())
+        })
+    }
+
+    fn update_locked<T>(&self, change: impl FnOnce(&mut Registry) -> Result<T>) -> Result<T> {
+        self.migrate()?;
+        let mut registry = self.read()?;
+        let result = change(&mut registry)?;
+        registry.version = VERSION;
+        self.write(&registry)?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pem_registry_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_legacy_skips_blank_lines_and_keeps_spaces_in_paths() {
+        let dir = temp_dir("legacy");
+        let path = dir.join("envs");
+        fs::write(
+            &path,
+            "data /home/me/venvs/data\n\n  web   /home/me/My Projects/web venv  \n",
+        )
+        .unwrap();
+        let registry = parse_legacy(&path).unwrap();
+        assert_eq!(registry.envs.len(), 2);
+        assert_eq!(
+            registry.envs["data"].path,
+            PathBuf::from("/home/me/venvs/data")
+        );
+        assert_eq!(
+            registry.envs["web"].path,
+            PathBuf::from("/home/me/My Projects/web venv")
+        );
+        assert!(registry.envs["web"].created.is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_rejects_newer_versions() {
+        let dir = temp_dir("version");
+        let store = Store::new(dir.clone());
+        fs::write(store.path(), format!("version = {}\n", VERSION + 1)).unwrap();
+        let err = store.read().unwrap_err();
+        assert!(err.to_string().contains("not supported by this pem"));
+        fs::write(store.path(), format!("version = {}\n", VERSION)).unwrap();
+        assert!(store.read().unwrap().envs.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use crate::python;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories which never hold environments, and may be large
+const SKIPPED: [&str; 5] = [
+    ".git",
+    "node_modules",
+    "__pycache__",
+    "site-packages",
+    ".cache",
+];
+
+/// Names of environments made inside a project, named after the project
+const GENERIC_NAMES: [&str; 5] = ["venv", ".venv", "env", ".env", "virtualenv"];
+
+/// Find the virtual and conda environments below a directory, without
+/// following links nor looking inside environments
+pub fn find_environments(dir: &Path, max_depth: usize, found: &mut Vec<PathBuf>) {
+    if python::is_venv(dir) || python::is_conda(dir) {
+        found.push(dir.to_path_buf());
+        // Conda installations hold their environments in `envs`
+        if python::is_conda(dir) && max_depth > 0 {
+            find_environments(&dir.join("envs"), max_depth - 1, found);
+        }
+        return;
+    }
+    if max_depth == 0 {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut dirs = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter(|entry| !SKIPPED.contains(&entry.file_name().to_string_lossy().as_ref()))
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    for dir in dirs {
+        find_environments(&dir, max_depth - 1, found);
+    }
+}
+
+/// Name for an environment, the one of the project containing it for
+/// generic names like `.venv`, made unique with a numbered suffix
+pub fn propose_name(path: &Path, taken: &HashSet<String>) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let base = if GENERIC_NAMES.contains(&name.as_str()) {
+        path.parent()
+            .and_then(|parent| parent.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(name)
+    } else {
+        name
+    };
+    let base = base.trim_start_matches('.').to_string();
+    if !taken.contains(&base) {
+        return base;
+    }
+    (2..)
+        .map(|i| format!("{}-{}", base, i))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
@@ -0,0 +1,135 @@
+use crate::python;
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use std::path::Path;
+
+/// Shells supported by the integration
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const POSIX_INIT: &str = r#"pem() {
+    case "$1" in
+        activate|deactivate)
+            local script
+            script="$(command pem "$@" --shell SHELL)" && eval "$script"
+            ;;
+        use)
+            command pem "$@" && _pem_hook
+            ;;
+        *)
+            command pem "$@"
+            ;;
+    esac
+}
+"#;
+
+const BASH_HOOK: &str = r#"_pem_hook() {
+    local script
+    script="$(command pem hook --shell bash)" && eval "$script"
+}
+_pem_prompt() {
+    if [ "$PWD" != "$_PEM_PWD" ]; then
+        _PEM_PWD="$PWD"
+        _pem_hook
+    fi
+}
+case ";${PROMPT_COMMAND-};" in
+    *";_pem_prompt;"*) ;;
+    *) PROMPT_COMMAND="_pem_prompt${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+"#;
+
+const ZSH_HOOK: &str = r#"_pem_hook() {
+    local script
+    script="$(command pem hook --shell zsh)" && eval "$script"
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _pem_hook
+_pem_hook
+"#;
+
+const FISH_INIT: &str = r#"function pem
+    switch $argv[1]
+        case activate deactivate
+            set -l script (command pem $argv --shell fish | string collect)
+            and eval $script
+        case use
+            command pem $argv
+            and _pem_hook
+        case '*'
+            command pem $argv
+    end
+end
+function _pem_hook --on-variable PWD
+    set -l script (command pem hook --shell fish | string collect)
+    and eval $script
+end
+_pem_hook
+"#;
+
+impl Shell {
+    /// Function wrapping the binary, so that `pem activate` changes the
+    /// environment of the current shell, and hook run when changing directory
+    pub fn init_script(&self) -> String {
+        match self {
+            Self::Bash => POSIX_INIT.replace("SHELL", "bash") + BASH_HOOK,
+            Self::Zsh => POSIX_INIT.replace("SHELL", "zsh") + ZSH_HOOK,
+            Self::Fish => FISH_INIT.to_string(),
+        }
+    }
+
+    /// Quote a string so that the shell reads it verbatim
+    pub fn quote(&self, text: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("'{}'", text.replace('\'', r"'\''")),
+            Self::Fish => format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'")),
+        }
+    }
+
+    pub fn export(&self, name: &str, value: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("export {}={}\n", name, self.quote(value)),
+            Self::Fish => format!("set -gx {} {}\n", name, self.quote(value)),
+        }
+    }
+
+    pub fn unset(&self, name: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("unset {}\n", name),
+            Self::Fish => format!("set -e {}\n", name),
+        }
+    }
+
+    /// Commands sourcing the activate script of the environment and exporting
+    /// its name in `PEM_ENV`
+    pub fn activate_script(&self, name: &str, env_path: &Path) -> Result<String> {
+        let script = match self {
+            Self::Bash | Self::Zsh => env_path.join("bin").join("activate"),
+            Self::Fish => env_path.join("bin").join("activate.fish"),
+        };
+        if !script.is_file() && python::is_conda(env_path) {
+            return Err(anyhow!(
+                "{} is a conda environment, activate it with 'conda activate {}'",
+                name,
+                env_path.display()
+            ));
+        }
+        if !script.is_file() {
+            return Err(anyhow!("{} does not exist", script.display()));
+        }
+        let script = self.quote(&script.to_string_lossy());
+        let source = match self {
+            Self::Bash | Self::Zsh => format!(". {}\n", script),
+            Self::Fish => format!("source {}\n", script),
+        };
+        Ok(source + &self.export("PEM_ENV", name) + &self.unset("PEM_AUTO"))
+    }
+
+    pub fn deactivate_script(&self) -> String {
+        "deactivate\n".to_string() + &self.unset("PEM_ENV") + &self.unset("PEM_AUTO")
+    }
+}
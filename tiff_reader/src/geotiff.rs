@@ -35,11 +35,12 @@ pub enum Endianness {
     Big,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub enum SampleFormat {
     UnsignedInt = 1,
     SignedInt = 2,
     Float = 3,
+    #[default]
     Undefined = 4,
 }
 
@@ -57,12 +58,6 @@ impl TryFrom<u16> for SampleFormat {
     }
 }
 
-impl Default for SampleFormat {
-    fn default() -> Self {
-        Self::Undefined
-    }
-}
-
 trait FromBytes: Sized {
     const SIZE: usize;
     fn from_le_bytes(bytes: &[u8]) -> Option<Self>;
@@ -99,7 +94,9 @@ macro_rules! impl_from_bytes {
 
 impl_from_bytes!(u16, u32, u64, i16, f32, f64);
 
+#[derive(Clone, Copy)]
 pub enum TiffDataType {
+    Ascii = 2,
     Short = 3,
     Long = 4,
     Float = 11,
@@ -111,6 +108,7 @@ impl TryFrom<u16> for TiffDataType {
 
     fn try_from(num: u16) -> Result<Self, Self::Error> {
         match num {
+            x if x == TiffDataType::Ascii as u16 => Ok(TiffDataType::Ascii),
             x if x == TiffDataType::Long as u16 => Ok(TiffDataType::Long),
             x if x == TiffDataType::Short as u16 => Ok(TiffDataType::Short),
             x if x == TiffDataType::Float as u16 => Ok(TiffDataType::Float),
@@ -120,6 +118,17 @@ impl TryFrom<u16> for TiffDataType {
     }
 }
 
+impl TiffDataType {
+    fn size(&self) -> usize {
+        match self {
+            Self::Ascii => 1,
+            Self::Short => 2,
+            Self::Long | Self::Float => 4,
+            Self::Double => 8,
+        }
+    }
+}
+
 pub enum TiffDataArray {
     UnsignedInt(Vec<u16>),
     SignedInt(Vec<i16>),
@@ -168,6 +177,16 @@ impl fmt::Display for TiffSample {
     }
 }
 
+/// Affine mapping from pixel indices to model coordinates, restricted to
+/// grids aligned with the axes. `(x0, y0)` is the corner of the first pixel.
+#[derive(Clone, Copy, Debug)]
+pub struct GeoTransform {
+    pub x0: f64,
+    pub dx: f64,
+    pub y0: f64,
+    pub dy: f64,
+}
+
 #[derive(Default)]
 struct TiffIfd {
     image_width: u32,
@@ -185,31 +204,28 @@ struct TiffIfd {
     model_tie_points: Option<Vec<f64>>,
     model_pixel_scale_tag: Option<Vec<f64>>,
     model_transformation_tag: Option<[f64; 16]>,
+    geo_key_directory_tag: Option<Vec<u16>>,
     geo_double_params_tag: Option<Vec<f64>>,
+    nodata: Option<f64>,
 }
 
 impl TiffIfd {
-    fn generate_coordinates(&self) -> Result<(Vec<f64>, Vec<f64>), TiffError> {
-        let nx = self.image_width as usize;
-        let ny = self.image_length as usize;
-        let mut x = vec![0.0; nx];
-        let mut y = vec![0.0; ny];
+    fn geo_transform(&self) -> Result<GeoTransform, TiffError> {
         if let Some(trans) = self.model_transformation_tag {
             if trans[1].abs() > f64::EPSILON || trans[4].abs() > f64::EPSILON {
                 return Err(TiffError::InvalidTransformation);
             }
-            for i in 0..nx {
-                x[i] = trans[3] + trans[0] * (i as f64);
-            }
-            for i in 0..ny {
-                y[i] = trans[7] + trans[5] * (i as f64);
-            }
-            return Ok((x, y));
+            return Ok(GeoTransform {
+                x0: trans[3],
+                dx: trans[0],
+                y0: trans[7],
+                dy: trans[5],
+            });
         }
-        if self.model_tie_points.is_some() && self.model_pixel_scale_tag.is_some() {
+        if let (Some(tie_points), Some(pixel_scale)) =
+            (&self.model_tie_points, &self.model_pixel_scale_tag)
+        {
             // Assume upper left corner is provided
-            let tie_points = self.model_tie_points.as_ref().unwrap();
-            let pixel_scale = self.model_pixel_scale_tag.as_ref().unwrap();
             if tie_points.len() != 6 {
                 eprintln!("model_tie_points has unexpected size");
                 return Err(TiffError::InvalidTransformation);
@@ -220,18 +236,34 @@ impl TiffIfd {
                 eprintln!("Provided tie point was not (0, 0)");
                 return Err(TiffError::InvalidTransformation);
             }
-            x[0] = tie_points[3];
-            for i in 1..nx {
-                x[i] = x[i - 1] + pixel_scale[0];
-            }
-            y[0] = tie_points[4];
-            for i in 1..ny {
-                y[i] = y[i - 1] - pixel_scale[1];
-            }
-            return Ok((x, y));
+            return Ok(GeoTransform {
+                x0: tie_points[3],
+                dx: pixel_scale[0],
+                y0: tie_points[4],
+                dy: -pixel_scale[1],
+            });
         }
         Err(TiffError::InvalidTransformation)
     }
+
+    fn generate_coordinates(&self) -> Result<(Vec<f64>, Vec<f64>), TiffError> {
+        let trans = self.geo_transform()?;
+        let x = (0..self.image_width)
+            .map(|i| trans.x0 + trans.dx * (i as f64))
+            .collect();
+        let y = (0..self.image_length)
+            .map(|i| trans.y0 + trans.dy * (i as f64))
+            .collect();
+        Ok((x, y))
+    }
+
+    fn rows_per_strip(&self) -> usize {
+        if self.rows_per_strip == 0 {
+            self.image_length as usize
+        } else {
+            self.rows_per_strip as usize
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -240,10 +272,13 @@ struct IfdEntry {
     field_type: u16,
     count: u32,
     value_offset: u32,
+    // Position of the value field, where values fitting in 4 bytes are stored
+    value_position: usize,
 }
 
 pub struct TiffDataset {
     ifd: TiffIfd,
+    transform: GeoTransform,
     x: Vec<f64>,
     y: Vec<f64>,
     data: Option<TiffDataArray>,
@@ -251,9 +286,11 @@ pub struct TiffDataset {
 
 impl TiffDataset {
     fn from_ifd(ifd: TiffIfd) -> Result<Self, TiffError> {
+        let transform = ifd.geo_transform()?;
         let (x, y) = ifd.generate_coordinates()?;
         Ok(Self {
             ifd,
+            transform,
             x,
             y,
             data: None,
@@ -269,7 +306,68 @@ impl TiffDataset {
     }
 
     pub fn get_sample_format(&self) -> SampleFormat {
-        self.ifd.sample_format.clone()
+        self.ifd.sample_format
+    }
+
+    pub fn get_bits_per_sample(&self) -> u16 {
+        self.ifd.bits_per_sample
+    }
+
+    pub fn get_transform(&self) -> GeoTransform {
+        self.transform
+    }
+
+    pub fn get_nodata(&self) -> Option<f64> {
+        self.ifd.nodata
+    }
+
+    pub fn get_geo_key_directory(&self) -> Option<&[u16]> {
+        self.ifd.geo_key_directory_tag.as_deref()
+    }
+
+    pub fn get_geo_double_params(&self) -> Option<&[f64]> {
+        self.ifd.geo_double_params_tag.as_deref()
+    }
+
+    pub fn get_projection(&self) -> &str {
+        &self.ifd.projection
+    }
+
+    /// Check that both datasets share the same coordinate reference system
+    pub fn same_crs(&self, other: &TiffDataset) -> bool {
+        self.ifd.geo_key_directory_tag == other.ifd.geo_key_directory_tag
+            && self.ifd.geo_double_params_tag == other.ifd.geo_double_params_tag
+            && self.ifd.projection == other.ifd.projection
+    }
+
+    /// Rows can only be streamed from uncompressed, single band, chunky images
+    /// of 16-bit integers or 32-bit floats, the samples `read_row` decodes
+    pub fn check_streamable(&self) -> Result<()> {
+        let supported = match self.ifd.sample_format {
+            SampleFormat::UnsignedInt | SampleFormat::SignedInt => self.ifd.bits_per_sample == 16,
+            SampleFormat::Float => self.ifd.bits_per_sample == 32,
+            SampleFormat::Undefined => false,
+        };
+        if !supported {
+            return Err(anyhow!(
+                "{}-bit {:?} samples are not supported, only 16-bit integers and 32-bit floats",
+                self.ifd.bits_per_sample,
+                self.ifd.sample_format
+            ));
+        }
+        if self.ifd.compression > 1 {
+            return Err(anyhow!("Compressed tiff is not supported"));
+        }
+        if self.ifd.samples_per_pixel > 1 {
+            return Err(anyhow!("Only single band tiff is supported"));
+        }
+        if self.ifd.planar_configuration > 1 {
+            return Err(anyhow!("Planar configuration is not supported"));
+        }
+        if self.ifd.strip_offsets.len() != self.ifd.strip_byte_counts.len() {
+            return Err(anyhow!("Strip offsets and byte counts do not match"));
+        }
+        Ok(())
     }
 
     pub fn shape(&self) -> (usize, usize) {
@@ -327,6 +425,28 @@ impl TiffDataset {
             TiffSample::F32(x) => Ok(x),
         }
     }
+
+    /// Read a single row straight from the file, without loading the whole image
+    pub fn read_row(&self, reader: &mut TiffReader, i: usize, row: &mut [f64]) -> Result<()> {
+        let nx = self.ifd.image_width as usize;
+        let bytesize = (self.ifd.bits_per_sample / 8) as usize;
+        let rows_per_strip = self.ifd.rows_per_strip();
+        let strip = i / rows_per_strip;
+        let Some(offset) = self.ifd.strip_offsets.get(strip) else {
+            return Err(anyhow!("Row {} is out of bounds", i));
+        };
+        let start = *offset as usize + (i % rows_per_strip) * nx * bytesize;
+        reader.offset = start;
+        for value in row.iter_mut().take(nx) {
+            *value = match self.ifd.sample_format {
+                SampleFormat::UnsignedInt => reader.read_scalar::<u16>() as f64,
+                SampleFormat::SignedInt => reader.read_scalar::<i16>() as f64,
+                SampleFormat::Float => reader.read_scalar::<f32>() as f64,
+                SampleFormat::Undefined => return Err(TiffError::UndefinedSampleFormat.into()),
+            };
+        }
+        Ok(())
+    }
 }
 
 pub struct TiffReader {
@@ -365,10 +485,22 @@ impl TiffReader {
         value
     }
 
+    /// Move to the values of an entry, which are stored inline when they fit in 4 bytes
+    fn seek_values(&mut self, entry: &IfdEntry) -> Result<(), TiffError> {
+        let data_type =
+            TiffDataType::try_from(entry.field_type).map_err(|_| TiffError::InvalidDataType)?;
+        if data_type.size() * entry.count as usize <= 4 {
+            self.offset = entry.value_position;
+        } else {
+            self.set_offset(entry.value_offset);
+        }
+        Ok(())
+    }
+
     fn read_vector<T: Num + NumCast>(&mut self, entry: &IfdEntry) -> Result<Vec<T>, TiffError> {
         let mut vec = Vec::new();
         let current = self.offset;
-        self.set_offset(entry.value_offset);
+        self.seek_values(entry)?;
         for _ in 0..entry.count {
             let num = match entry.field_type.try_into() {
                 Ok(TiffDataType::Float) => NumCast::from(self.read_scalar::<f32>()).unwrap(),
@@ -383,12 +515,34 @@ impl TiffReader {
         Ok(vec)
     }
 
+    fn read_ascii(&mut self, entry: &IfdEntry) -> Result<String, TiffError> {
+        let current = self.offset;
+        self.seek_values(entry)?;
+        let start = self.offset;
+        let stop = start + entry.count as usize;
+        self.offset = current;
+        Ok(String::from_utf8_lossy(&self.data[start..stop]).to_string())
+    }
+
     fn read_ifd_entry(&mut self) -> IfdEntry {
+        let tag = self.read_scalar();
+        let field_type = self.read_scalar();
+        let count = self.read_scalar();
+        let value_position = self.offset;
+        // A single short is left-justified in the value field
+        let value_offset = if field_type == TiffDataType::Short as u16 && count == 1 {
+            let value: u16 = self.read_scalar();
+            self.offset += 2;
+            value as u32
+        } else {
+            self.read_scalar()
+        };
         IfdEntry {
-            tag: self.read_scalar(),
-            field_type: self.read_scalar(),
-            count: self.read_scalar(),
-            value_offset: self.read_scalar(),
+            tag,
+            field_type,
+            count,
+            value_offset,
+            value_position,
         }
     }
 
@@ -412,12 +566,13 @@ impl TiffReader {
                 let vec = self.read_vector::<f64>(&entry)?;
                 ifd.model_transformation_tag = Some(vec[..16].try_into()?);
             }
-            34735 => {}
+            34735 => ifd.geo_key_directory_tag = Some(self.read_vector(&entry)?),
             34736 => ifd.geo_double_params_tag = Some(self.read_vector(&entry)?),
-            34737 => {
-                let start = entry.value_offset as usize;
-                let stop = start + entry.count as usize;
-                ifd.projection = String::from_utf8_lossy(&self.data[start..stop]).to_string();
+            34737 => ifd.projection = self.read_ascii(&entry)?,
+            // GDAL_NODATA
+            42113 => {
+                let nodata = self.read_ascii(&entry)?;
+                ifd.nodata = nodata.trim_end_matches('\0').trim().parse().ok();
             }
             _ => println!("Unknown IFD entry {:?}", entry),
         };
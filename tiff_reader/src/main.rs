@@ -1,13 +1,48 @@
 mod geotiff;
+mod mosaic;
+mod writer;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use clap::{Parser, Subcommand};
 use geotiff::{SampleFormat, TiffReader};
 use memmap2::Mmap;
-use std::env;
+use mosaic::OverlapPolicy;
 use std::fs::File;
+use std::path::{Path, PathBuf};
 
-fn main() -> Result<()> {
-    let filename = env::args().nth(1).expect("Missing input file");
+#[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// GeoTIFF file to inspect
+    file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Merge adjacent GeoTIFF tiles sharing the same CRS and resolution
+    Mosaic {
+        /// Input tiles
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output GeoTIFF file
+        #[arg(long, short)]
+        output: PathBuf,
+
+        /// How to combine overlapping tiles
+        #[arg(long, short, value_enum, default_value_t = OverlapPolicy::FirstValid)]
+        policy: OverlapPolicy,
+
+        /// Nodata value of the output [default: nodata of the first tile]
+        #[arg(long, allow_hyphen_values = true)]
+        nodata: Option<f64>,
+    },
+}
+
+fn inspect(filename: &Path) -> Result<()> {
     let file = File::open(filename)?;
     let map = unsafe { Mmap::map(&file)? };
     let mut tiff_reader = TiffReader::new(map)?;
@@ -23,3 +58,19 @@ fn main() -> Result<()> {
     }
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Commands::Mosaic {
+            inputs,
+            output,
+            policy,
+            nodata,
+        }) => mosaic::mosaic(inputs, output, *policy, *nodata),
+        None => match &cli.file {
+            Some(filename) => inspect(filename),
+            None => Err(anyhow!("Missing input file")),
+        },
+    }
+}
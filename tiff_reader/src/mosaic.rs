@@ -0,0 +1,249 @@
+use crate::geotiff::{GeoTransform, SampleFormat, TiffDataset, TiffReader};
+use crate::writer::{Layout, TiffWriter};
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+// Relative tolerance used to compare resolutions and grid alignments
+const GRID_TOLERANCE: f64 = 1e-6;
+
+/// How to combine valid samples where tiles overlap
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OverlapPolicy {
+    /// Keep the value of the first tile providing a valid sample
+    FirstValid,
+    /// Keep the value of the last tile providing a valid sample
+    LastValid,
+    /// Average all valid samples
+    Mean,
+}
+
+struct Tile {
+    reader: TiffReader,
+    dataset: TiffDataset,
+    row_offset: usize,
+    col_offset: usize,
+}
+
+impl Tile {
+    fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let map = unsafe { Mmap::map(&file)? };
+        let mut reader = TiffReader::new(map)?;
+        let dataset = reader.read_tiff()?;
+        dataset
+            .check_streamable()
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Ok(Self {
+            reader,
+            dataset,
+            row_offset: 0,
+            col_offset: 0,
+        })
+    }
+
+    /// Nodata is compared in the precision of the samples, so that a value
+    /// like -9999.9 matches once rounded to f32
+    fn is_valid(&self, value: f64) -> bool {
+        !value.is_nan()
+            && self
+                .dataset
+                .get_nodata()
+                .is_none_or(|nodata| nodata as f32 != value as f32)
+    }
+}
+
+fn grid_offset(origin: f64, tile_origin: f64, step: f64) -> Result<usize> {
+    let offset = (tile_origin - origin) / step;
+    if (offset - offset.round()).abs() > GRID_TOLERANCE * offset.abs().max(1.0) {
+        return Err(anyhow!("Tiles are not aligned on the same grid"));
+    }
+    Ok(offset.round() as usize)
+}
+
+fn check_compatible(reference: &TiffDataset, dataset: &TiffDataset, path: &Path) -> Result<()> {
+    if !reference.same_crs(dataset) {
+        return Err(anyhow!("{}: CRS differs from first tile", path.display()));
+    }
+    if reference.get_sample_format() != dataset.get_sample_format()
+        || reference.get_bits_per_sample() != dataset.get_bits_per_sample()
+    {
+        return Err(anyhow!(
+            "{}: data type differs from first tile",
+            path.display()
+        ));
+    }
+    let a = reference.get_transform();
+    let b = dataset.get_transform();
+    if (a.dx - b.dx).abs() > GRID_TOLERANCE * a.dx.abs()
+        || (a.dy - b.dy).abs() > GRID_TOLERANCE * a.dy.abs()
+    {
+        return Err(anyhow!(
+            "{}: resolution differs from first tile",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Compute the transform and shape of the union of all tiles, and record the
+/// position of each tile in the output grid.
+fn union_grid(tiles: &mut [Tile]) -> Result<(GeoTransform, (usize, usize))> {
+    let reference = tiles[0].dataset.get_transform();
+    if reference.dx <= 0.0 || reference.dy >= 0.0 {
+        return Err(anyhow!("Only north-up rasters can be mosaicked"));
+    }
+    let (mut x0, mut x1) = (f64::INFINITY, f64::NEG_INFINITY);
+    let (mut y0, mut y1) = (f64::NEG_INFINITY, f64::INFINITY);
+    for tile in tiles.iter() {
+        let trans = tile.dataset.get_transform();
+        let (ny, nx) = tile.dataset.shape();
+        x0 = x0.min(trans.x0);
+        x1 = x1.max(trans.x0 + trans.dx * nx as f64);
+        y0 = y0.max(trans.y0);
+        y1 = y1.min(trans.y0 + trans.dy * ny as f64);
+    }
+    let transform = GeoTransform {
+        x0,
+        dx: reference.dx,
+        y0,
+        dy: reference.dy,
+    };
+    for tile in tiles.iter_mut() {
+        let trans = tile.dataset.get_transform();
+        tile.col_offset = grid_offset(x0, trans.x0, transform.dx)?;
+        tile.row_offset = grid_offset(y0, trans.y0, transform.dy)?;
+    }
+    let nx = ((x1 - x0) / transform.dx).round() as usize;
+    let ny = ((y1 - y0) / transform.dy).round() as usize;
+    Ok((transform, (ny, nx)))
+}
+
+/// Whether some pixels of the output grid are covered by no tile
+fn has_gaps(tiles: &[Tile], (ny, nx): (usize, usize)) -> bool {
+    (0..ny).any(|i| {
+        let mut spans = tiles
+            .iter()
+            .filter(|tile| i >= tile.row_offset && i < tile.row_offset + tile.dataset.shape().0)
+            .map(|tile| (tile.col_offset, tile.col_offset + tile.dataset.shape().1))
+            .collect::<Vec<_>>();
+        spans.sort();
+        let mut covered = 0;
+        for (start, end) in spans {
+            if start > covered {
+                return true;
+            }
+            covered = covered.max(end);
+        }
+        covered < nx
+    })
+}
+
+/// Check that the nodata value can be stored in the samples
+fn check_nodata(sample_format: SampleFormat, nodata: f64) -> Result<()> {
+    let (min, max) = match sample_format {
+        SampleFormat::UnsignedInt => (u16::MIN as f64, u16::MAX as f64),
+        SampleFormat::SignedInt => (i16::MIN as f64, i16::MAX as f64),
+        _ => return Ok(()),
+    };
+    if nodata.fract() != 0.0 || nodata < min || nodata > max {
+        return Err(anyhow!(
+            "Nodata value {} cannot be stored in {:?} samples",
+            nodata,
+            sample_format
+        ));
+    }
+    Ok(())
+}
+
+/// Merge adjacent tiles into a single GeoTIFF. Rows are streamed from the
+/// inputs one at a time, so only a few rows are kept in memory.
+/// The nodata value of the output is the given one, else the one of the
+/// first tile, if any.
+pub fn mosaic(
+    inputs: &[PathBuf],
+    output: &Path,
+    policy: OverlapPolicy,
+    nodata: Option<f64>,
+) -> Result<()> {
+    if inputs.is_empty() {
+        return Err(anyhow!("No input tile provided"));
+    }
+    // Truncating a tile which is still mapped would crash the process
+    if let Ok(out) = fs::metadata(output) {
+        for path in inputs.iter() {
+            let input = fs::metadata(path)?;
+            if (input.dev(), input.ino()) == (out.dev(), out.ino()) {
+                return Err(anyhow!("{}: output is also an input", output.display()));
+            }
+        }
+    }
+    let mut tiles = inputs
+        .iter()
+        .map(|path| Tile::open(path))
+        .collect::<Result<Vec<_>>>()?;
+    for (tile, path) in tiles.iter().zip(inputs).skip(1) {
+        check_compatible(&tiles[0].dataset, &tile.dataset, path)?;
+    }
+    let (transform, (ny, nx)) = union_grid(&mut tiles)?;
+    let sample_format = tiles[0].dataset.get_sample_format();
+    let nodata = nodata.or(tiles[0].dataset.get_nodata());
+    if let Some(nodata) = nodata {
+        check_nodata(sample_format, nodata)?;
+    }
+    // Value of the pixels without any valid sample
+    let fill = match (nodata, sample_format) {
+        (Some(nodata), _) => nodata,
+        (None, SampleFormat::Float) => f64::NAN,
+        // Any integer may be a valid sample, like 0 at sea level in a DEM
+        (None, _) if has_gaps(&tiles, (ny, nx)) => {
+            return Err(anyhow!(
+                "Tiles leave gaps and declare no nodata value, give one with --nodata"
+            ));
+        }
+        // Every pixel gets a valid sample, integer tiles without nodata having no invalid one
+        (None, _) => 0.0,
+    };
+    let layout = Layout::of(&tiles[0].dataset);
+    let mut writer = TiffWriter::create(output, layout, (ny, nx), transform, nodata)?;
+    let mut values = vec![0.0; nx];
+    let mut counts = vec![0u32; nx];
+    let mut buffer = Vec::new();
+    for i in 0..ny {
+        values.fill(0.0);
+        counts.fill(0);
+        for tile in tiles.iter_mut() {
+            let (tile_ny, tile_nx) = tile.dataset.shape();
+            if i < tile.row_offset || i >= tile.row_offset + tile_ny {
+                continue;
+            }
+            buffer.resize(tile_nx, 0.0);
+            tile.dataset
+                .read_row(&mut tile.reader, i - tile.row_offset, &mut buffer)?;
+            for (j, &value) in buffer.iter().enumerate() {
+                if !tile.is_valid(value) {
+                    continue;
+                }
+                let k = tile.col_offset + j;
+                match policy {
+                    OverlapPolicy::FirstValid if counts[k] > 0 => continue,
+                    OverlapPolicy::FirstValid | OverlapPolicy::LastValid => values[k] = value,
+                    OverlapPolicy::Mean => values[k] += value,
+                }
+                counts[k] += 1;
+            }
+        }
+        for (value, &count) in values.iter_mut().zip(counts.iter()) {
+            if count == 0 {
+                *value = fill;
+            } else if matches!(policy, OverlapPolicy::Mean) {
+                *value /= count as f64;
+            }
+        }
+        writer.write_row(&values)?;
+    }
+    writer.finish()
+}
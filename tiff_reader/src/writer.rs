@@ -0,0 +1,373 @@
+use crate::geotiff::{GeoTransform, SampleFormat, TiffDataType, TiffDataset};
+use anyhow::{Result, anyhow};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+struct IfdValue {
+    tag: u16,
+    field_type: TiffDataType,
+    count: u32,
+    bytes: Vec<u8>,
+}
+
+impl IfdValue {
+    fn shorts(tag: u16, values: &[u16]) -> Self {
+        Self {
+            tag,
+            field_type: TiffDataType::Short,
+            count: values.len() as u32,
+            bytes: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    fn longs(tag: u16, values: &[u32]) -> Self {
+        Self {
+            tag,
+            field_type: TiffDataType::Long,
+            count: values.len() as u32,
+            bytes: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    fn doubles(tag: u16, values: &[f64]) -> Self {
+        Self {
+            tag,
+            field_type: TiffDataType::Double,
+            count: values.len() as u32,
+            bytes: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    fn ascii(tag: u16, value: &str) -> Self {
+        let mut bytes = value.as_bytes().to_vec();
+        if bytes.last() != Some(&0) {
+            bytes.push(0);
+        }
+        Self {
+            tag,
+            field_type: TiffDataType::Ascii,
+            count: bytes.len() as u32,
+            bytes,
+        }
+    }
+}
+
+/// Sample type and coordinate reference system of a written raster
+#[derive(Clone, Debug)]
+pub struct Layout {
+    pub sample_format: SampleFormat,
+    pub bits_per_sample: u16,
+    pub geo_key_directory: Option<Vec<u16>>,
+    pub geo_double_params: Option<Vec<f64>>,
+    pub projection: String,
+}
+
+impl Layout {
+    /// The sample type and CRS of an existing dataset
+    pub fn of(dataset: &TiffDataset) -> Self {
+        Self {
+            sample_format: dataset.get_sample_format(),
+            bits_per_sample: dataset.get_bits_per_sample(),
+            geo_key_directory: dataset.get_geo_key_directory().map(|v| v.to_vec()),
+            geo_double_params: dataset.get_geo_double_params().map(|v| v.to_vec()),
+            projection: dataset.get_projection().to_string(),
+        }
+    }
+}
+
+/// Little endian GeoTIFF writer storing one uncompressed row per strip, so
+/// that rows can be written one at a time.
+pub struct TiffWriter {
+    file: BufWriter<File>,
+    position: u64,
+    width: usize,
+    height: usize,
+    sample_format: SampleFormat,
+    bits_per_sample: u16,
+    transform: GeoTransform,
+    nodata: Option<f64>,
+    geo_key_directory: Option<Vec<u16>>,
+    geo_double_params: Option<Vec<f64>>,
+    projection: String,
+    strip_offsets: Vec<u32>,
+}
+
+impl TiffWriter {
+    /// Create a writer, which records the nodata value only when there is one
+    pub fn create(
+        path: &Path,
+        layout: Layout,
+        shape: (usize, usize),
+        transform: GeoTransform,
+        nodata: Option<f64>,
+    ) -> Result<Self> {
+        let (height, width) = shape;
+        let mut file = BufWriter::new(File::create(path)?);
+        // Header, the IFD offset is patched once all the rows are written
+        file.write_all(b"II")?;
+        file.write_all(&42u16.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            file,
+            position: 8,
+            width,
+            height,
+            sample_format: layout.sample_format,
+            bits_per_sample: layout.bits_per_sample,
+            transform,
+            nodata,
+            geo_key_directory: layout.geo_key_directory,
+            geo_double_params: layout.geo_double_params,
+            projection: layout.projection,
+            strip_offsets: Vec::with_capacity(height),
+        })
+    }
+
+    fn offset(&self) -> Result<u32> {
+        u32::try_from(self.position).map_err(|_| anyhow!("Output exceeds the 4GB tiff limit"))
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.file.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn row_bytes(&self) -> usize {
+        self.width * (self.bits_per_sample / 8) as usize
+    }
+
+    pub fn write_row(&mut self, row: &[f64]) -> Result<()> {
+        if row.len() != self.width {
+            return Err(anyhow!("Expected row of {} values", self.width));
+        }
+        if self.strip_offsets.len() == self.height {
+            return Err(anyhow!("All {} rows were already written", self.height));
+        }
+        let offset = self.offset()?;
+        self.strip_offsets.push(offset);
+        let mut bytes = Vec::with_capacity(self.row_bytes());
+        for &value in row {
+            match self.sample_format {
+                SampleFormat::UnsignedInt => {
+                    let x = value.round().clamp(u16::MIN as f64, u16::MAX as f64) as u16;
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                }
+                SampleFormat::SignedInt => {
+                    let x = value.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                }
+                SampleFormat::Float => bytes.extend_from_slice(&(value as f32).to_le_bytes()),
+                SampleFormat::Undefined => return Err(anyhow!("Undefined sample format")),
+            }
+        }
+        self.write_bytes(&bytes)
+    }
+
+    fn ifd_values(&self) -> Vec<IfdValue> {
+        let row_bytes = self.row_bytes() as u32;
+        let mut values = vec![
+            IfdValue::longs(256, &[self.width as u32]),
+            IfdValue::longs(257, &[self.height as u32]),
+            IfdValue::shorts(258, &[self.bits_per_sample]),
+            IfdValue::shorts(259, &[1]),
+            IfdValue::shorts(262, &[1]),
+            IfdValue::longs(273, &self.strip_offsets),
+            IfdValue::shorts(277, &[1]),
+            IfdValue::longs(278, &[1]),
+            IfdValue::longs(279, &vec![row_bytes; self.height]),
+            IfdValue::shorts(284, &[1]),
+            IfdValue::shorts(339, &[self.sample_format as u16]),
+            IfdValue::doubles(33550, &[self.transform.dx, -self.transform.dy, 0.0]),
+            IfdValue::doubles(
+                33922,
+                &[0.0, 0.0, 0.0, self.transform.x0, self.transform.y0, 0.0],
+            ),
+        ];
+        if let Some(keys) = &self.geo_key_directory {
+            values.push(IfdValue::shorts(34735, keys));
+        }
+        if let Some(params) = &self.geo_double_params {
+            values.push(IfdValue::doubles(34736, params));
+        }
+        if !self.projection.is_empty() {
+            values.push(IfdValue::ascii(34737, &self.projection));
+        }
+        if let Some(nodata) = self.nodata {
+            values.push(IfdValue::ascii(42113, &nodata.to_string()));
+        }
+        values
+    }
+
+    /// Write the image file directory and close the file
+    pub fn finish(mut self) -> Result<()> {
+        if self.strip_offsets.len() != self.height {
+            return Err(anyhow!(
+                "Only {} out of {} rows were written",
+                self.strip_offsets.len(),
+                self.height
+            ));
+        }
+        if self.position % 2 == 1 {
+            self.write_bytes(&[0])?;
+        }
+        let values = self.ifd_values();
+        let ifd_offset = self.offset()?;
+        let mut extra_offset = self.position + 2 + 12 * values.len() as u64 + 4;
+        let mut entries = Vec::new();
+        let mut extra = Vec::new();
+        entries.extend_from_slice(&(values.len() as u16).to_le_bytes());
+        for value in values.iter() {
+            entries.extend_from_slice(&value.tag.to_le_bytes());
+            entries.extend_from_slice(&(value.field_type as u16).to_le_bytes());
+            entries.extend_from_slice(&value.count.to_le_bytes());
+            if value.bytes.len() <= 4 {
+                let mut inline = [0u8; 4];
+                inline[..value.bytes.len()].copy_from_slice(&value.bytes);
+                entries.extend_from_slice(&inline);
+            } else {
+                let offset = u32::try_from(extra_offset)
+                    .map_err(|_| anyhow!("Output exceeds the 4GB tiff limit"))?;
+                entries.extend_from_slice(&offset.to_le_bytes());
+                extra.extend_from_slice(&value.bytes);
+                if value.bytes.len() % 2 == 1 {
+                    extra.push(0);
+                }
+                extra_offset += value.bytes.len().next_multiple_of(2) as u64;
+            }
+        }
+        entries.extend_from_slice(&0u32.to_le_bytes());
+        self.write_bytes(&entries)?;
+        self.write_bytes(&extra)?;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&ifd_offset.to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geotiff::TiffReader;
+    use memmap2::Mmap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tiff_writer_{}_{}.tif", std::process::id(), name))
+    }
+
+    fn layout(sample_format: SampleFormat, bits_per_sample: u16) -> Layout {
+        Layout {
+            sample_format,
+            bits_per_sample,
+            geo_key_directory: Some(vec![1, 1, 0, 2, 1024, 0, 1, 1, 3072, 0, 1, 32631]),
+            geo_double_params: None,
+            // Odd length with the NUL terminator, so that the nodata value after it
+            // needs padding
+            projection: "WGS 84 / UTM 31N".to_string(),
+        }
+    }
+
+    /// Write the rows, then read back the dataset and its rows
+    fn round_trip(
+        name: &str,
+        layout: Layout,
+        transform: GeoTransform,
+        nodata: Option<f64>,
+        rows: &[Vec<f64>],
+    ) -> (crate::geotiff::TiffDataset, Vec<Vec<f64>>) {
+        let path = temp_path(name);
+        let shape = (rows.len(), rows[0].len());
+        let mut writer = TiffWriter::create(&path, layout, shape, transform, nodata).unwrap();
+        for row in rows {
+            writer.write_row(row).unwrap();
+        }
+        writer.finish().unwrap();
+        let map = unsafe { Mmap::map(&File::open(&path).unwrap()).unwrap() };
+        let mut reader = TiffReader::new(map).unwrap();
+        let dataset = reader.read_tiff().unwrap();
+        dataset.check_streamable().unwrap();
+        let read = (0..shape.0)
+            .map(|i| {
+                let mut row = vec![0.0; shape.1];
+                dataset.read_row(&mut reader, i, &mut row).unwrap();
+                row
+            })
+            .collect();
+        fs::remove_file(&path).unwrap();
+        (dataset, read)
+    }
+
+    #[test]
+    fn round_trip_int16() {
+        let transform = GeoTransform {
+            x0: 500000.0,
+            dx: 30.0,
+            y0: 4650000.0,
+            dy: -30.0,
+        };
+        let rows = vec![
+            vec![0.0, 5.0, -32768.0],
+            vec![3.0, -1.0, 32767.0],
+            vec![7.0, 0.0, 12.0],
+        ];
+        let layout = layout(SampleFormat::SignedInt, 16);
+        let (dataset, read) = round_trip("int16", layout, transform, Some(-32768.0), &rows);
+        assert_eq!(read, rows);
+        assert_eq!(dataset.shape(), (3, 3));
+        assert_eq!(dataset.get_sample_format(), SampleFormat::SignedInt);
+        assert_eq!(dataset.get_nodata(), Some(-32768.0));
+        let read_transform = dataset.get_transform();
+        assert_eq!(
+            (
+                read_transform.x0,
+                read_transform.dx,
+                read_transform.y0,
+                read_transform.dy
+            ),
+            (500000.0, 30.0, 4650000.0, -30.0)
+        );
+        // ASCII values are read with their NUL terminator
+        assert_eq!(
+            dataset.get_projection().trim_end_matches('\0'),
+            "WGS 84 / UTM 31N"
+        );
+        assert_eq!(
+            dataset.get_geo_key_directory(),
+            Some(&[1, 1, 0, 2, 1024, 0, 1, 1, 3072, 0, 1, 32631][..])
+        );
+    }
+
+    #[test]
+    fn round_trip_float32_without_nodata() {
+        let transform = GeoTransform {
+            x0: -10.0,
+            dx: 0.5,
+            y0: 45.0,
+            dy: -0.25,
+        };
+        // A single row, so that the strip offset is stored inline
+        let rows = vec![vec![1.5, -9999.9, 0.0, 2.25, 3.0]];
+        let layout = layout(SampleFormat::Float, 32);
+        let (dataset, read) = round_trip("float32", layout, transform, None, &rows);
+        let expected = rows[0]
+            .iter()
+            .map(|&value| value as f32 as f64)
+            .collect::<Vec<_>>();
+        assert_eq!(read, vec![expected]);
+        assert_eq!(dataset.get_nodata(), None);
+        let read_transform = dataset.get_transform();
+        assert_eq!(
+            (
+                read_transform.x0,
+                read_transform.dx,
+                read_transform.y0,
+                read_transform.dy
+            ),
+            (-10.0, 0.5, 45.0, -0.25)
+        );
+    }
+}
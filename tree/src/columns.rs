@@ -0,0 +1,133 @@
+use chrono::{DateTime, Local, TimeDelta};
+use std::fs::Metadata;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use uzers::{Users, UsersCache};
+
+/// Which metadata columns are printed in front of each entry
+#[derive(Clone, Copy, Debug)]
+pub struct ColumnOptions {
+    pub size: bool,
+    pub human: bool,
+    pub permissions: bool,
+    pub owner: bool,
+    pub date: bool,
+}
+
+pub struct Columns {
+    options: ColumnOptions,
+    users: UsersCache,
+}
+
+/// Format a size like GNU tree does with `-h`
+pub fn human_size(size: u64) -> String {
+    const UNITS: [char; 7] = ['B', 'K', 'M', 'G', 'T', 'P', 'E'];
+    if size < 1024 {
+        return format!("{:>4}", size);
+    }
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 9.95 {
+        format!("{:3.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:3.0}{}", value, UNITS[unit])
+    }
+}
+
+/// Format the file type and permission bits like `ls -l`
+pub fn permissions_string(metadata: &Metadata) -> String {
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_dir() {
+        'd'
+    } else if file_type.is_file() {
+        '-'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_fifo() {
+        'p'
+    } else {
+        's'
+    };
+    let mode = metadata.permissions().mode();
+    let mut result = String::with_capacity(10);
+    result.push(kind);
+    for (shift, special, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        result.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        result.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        result.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    result
+}
+
+/// Format a modification time, showing the year instead of the time for
+/// files older than six months like `ls -l`
+pub fn date_string(metadata: &Metadata) -> String {
+    let Ok(modified) = metadata.modified() else {
+        return format!("{:12}", "?");
+    };
+    let date: DateTime<Local> = modified.into();
+    if Local::now() - date > TimeDelta::days(182) {
+        date.format("%b %e  %Y").to_string()
+    } else {
+        date.format("%b %e %H:%M").to_string()
+    }
+}
+
+impl Columns {
+    pub fn new(options: ColumnOptions) -> Self {
+        Self {
+            options,
+            users: UsersCache::new(),
+        }
+    }
+
+    pub fn options(&self) -> ColumnOptions {
+        self.options
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let options = &self.options;
+        !(options.size || options.human || options.permissions || options.owner || options.date)
+    }
+
+    pub fn owner(&self, uid: u32) -> String {
+        match self.users.get_user_by_uid(uid) {
+            Some(user) => user.name().to_string_lossy().to_string(),
+            None => uid.to_string(),
+        }
+    }
+
+    /// Format the columns of an entry, `size` being the aggregated size of
+    /// directories when disk usage is requested
+    pub fn format(&self, metadata: &Metadata, size: u64) -> String {
+        let mut fields = Vec::new();
+        if self.options.permissions {
+            fields.push(permissions_string(metadata));
+        }
+        if self.options.owner {
+            fields.push(format!("{:<8}", self.owner(metadata.uid())));
+        }
+        if self.options.human {
+            fields.push(human_size(size));
+        } else if self.options.size {
+            fields.push(format!("{:>11}", size));
+        }
+        if self.options.date {
+            fields.push(date_string(metadata));
+        }
+        format!("[{}]", fields.join(" "))
+    }
+}
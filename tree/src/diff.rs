@@ -0,0 +1,251 @@
+use crate::json::entry_type;
+use crate::render::{display_name, display_path, Charset, Connectors};
+use crate::theme::Theme;
+use crate::walk::Node;
+use anyhow::Result;
+use colored::Colorize;
+use rayon::prelude::*;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// Only in the other tree
+    Added,
+    /// Only in the walked tree
+    Removed,
+    Changed,
+}
+
+impl Change {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+
+    fn marker(&self) -> &'static str {
+        match self {
+            Self::Added => "+",
+            Self::Removed => "-",
+            Self::Changed => "~",
+        }
+    }
+}
+
+/// An entry of either tree, which differs or holds differing entries
+pub struct DiffEntry<'a> {
+    /// Entry of the other tree when added, else of the walked tree
+    node: &'a Node,
+    change: Option<Change>,
+    children: Vec<DiffEntry<'a>>,
+}
+
+/// Whether two files have the same content, read chunk by chunk
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let mut a = BufReader::new(File::open(a)?);
+    let mut b = BufReader::new(File::open(b)?);
+    let mut a_buffer = [0u8; 8192];
+    let mut b_buffer = [0u8; 8192];
+    loop {
+        let n = a.read(&mut a_buffer)?;
+        if n == 0 {
+            return Ok(b.read(&mut b_buffer)? == 0);
+        }
+        if b.read_exact(&mut b_buffer[..n]).is_err() || a_buffer[..n] != b_buffer[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Compare two entries of the same name, except the content of directories.
+/// Files differ by size and modification time, or by content with `checksum`.
+fn differs(a: &Node, b: &Node, checksum: bool) -> bool {
+    if a.is_real_dir() != b.is_real_dir() || a.link.is_some() != b.link.is_some() {
+        return true;
+    }
+    if let (Some(a), Some(b)) = (&a.link, &b.link) {
+        return a.target != b.target;
+    }
+    if a.is_real_dir() {
+        return false;
+    }
+    if a.metadata.len() != b.metadata.len() {
+        return true;
+    }
+    if checksum {
+        !same_content(&a.path, &b.path).unwrap_or(false)
+    } else {
+        a.metadata.modified().ok() != b.metadata.modified().ok()
+    }
+}
+
+/// An entry only present in one tree, along with all its content
+fn whole(node: &Node, change: Change) -> DiffEntry<'_> {
+    DiffEntry {
+        node,
+        change: Some(change),
+        children: node
+            .children
+            .iter()
+            .map(|child| whole(child, change))
+            .collect(),
+    }
+}
+
+/// Compare the content of two directories, keeping only the differences
+pub fn diff<'a>(left: &'a Node, right: &'a Node, checksum: bool) -> Vec<DiffEntry<'a>> {
+    let right_children = right
+        .children
+        .iter()
+        .map(|child| (child.name.as_str(), child))
+        .collect::<HashMap<_, _>>();
+    let mut entries = left
+        .children
+        .par_iter()
+        .filter_map(|a| {
+            let Some(b) = right_children.get(a.name.as_str()) else {
+                return Some(whole(a, Change::Removed));
+            };
+            if differs(a, b, checksum) {
+                return Some(DiffEntry {
+                    node: a,
+                    change: Some(Change::Changed),
+                    children: Vec::new(),
+                });
+            }
+            let children = diff(a, b, checksum);
+            (!children.is_empty()).then_some(DiffEntry {
+                node: a,
+                change: None,
+                children,
+            })
+        })
+        .collect::<Vec<_>>();
+    let left_names = left
+        .children
+        .iter()
+        .map(|child| child.name.as_str())
+        .collect::<HashSet<_>>();
+    entries.extend(
+        right
+            .children
+            .iter()
+            .filter(|child| !left_names.contains(child.name.as_str()))
+            .map(|child| whole(child, Change::Added)),
+    );
+    entries.sort_by(|a, b| a.node.name.cmp(&b.node.name));
+    entries
+}
+
+fn count(entries: &[DiffEntry], counts: &mut HashMap<&'static str, usize>) {
+    for entry in entries.iter() {
+        if let Some(change) = entry.change {
+            *counts.entry(change.name()).or_default() += 1;
+        }
+        count(&entry.children, counts);
+    }
+}
+
+/// Report like "2 added, 1 removed, 3 changed"
+pub fn report(entries: &[DiffEntry]) -> String {
+    let mut counts = HashMap::new();
+    count(entries, &mut counts);
+    [Change::Added, Change::Removed, Change::Changed]
+        .iter()
+        .map(|change| {
+            let name = change.name();
+            format!("{} {}", counts.get(name).unwrap_or(&0), name)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render the differences with the connectors of the tree, prefixing the
+/// entries with "+", "-" or "~"
+pub struct DiffRenderer {
+    connectors: Connectors,
+    theme: Theme,
+}
+
+impl DiffRenderer {
+    pub fn new(charset: Charset, theme: Theme) -> Self {
+        Self {
+            connectors: charset.connectors(),
+            theme,
+        }
+    }
+
+    fn render_entries(
+        &self,
+        entries: &[DiffEntry],
+        prefix: &str,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        for (i, entry) in entries.iter().enumerate() {
+            let is_last = i == entries.len() - 1;
+            let connector = if is_last {
+                self.connectors.last
+            } else {
+                self.connectors.branch
+            };
+            write!(out, "{}{}", prefix, connector)?;
+            match entry.change {
+                Some(change) => {
+                    let text = format!(
+                        "{} {}",
+                        change.marker(),
+                        display_name(entry.node, &self.theme)
+                    );
+                    let text = match change {
+                        Change::Added => text.green(),
+                        Change::Removed => text.red(),
+                        Change::Changed => text.yellow(),
+                    };
+                    writeln!(out, "{}", text)?;
+                }
+                None => writeln!(out, "{}", display_path(entry.node, &self.theme))?,
+            }
+            let new_prefix = if is_last {
+                format!("{}{}", prefix, self.connectors.space)
+            } else {
+                format!("{}{}", prefix, self.connectors.vertical)
+            };
+            self.render_entries(&entry.children, &new_prefix, out)?;
+        }
+        Ok(())
+    }
+
+    pub fn render(&self, root: &Node, entries: &[DiffEntry], out: &mut impl Write) -> Result<()> {
+        writeln!(out, "{}", display_path(root, &self.theme))?;
+        self.render_entries(entries, "", out)
+    }
+}
+
+fn flatten(entries: &[DiffEntry], parent: &Path, values: &mut Vec<serde_json::Value>) {
+    for entry in entries.iter() {
+        let path = parent.join(&entry.node.name);
+        if let Some(change) = entry.change {
+            values.push(json!({
+                "change": change.name(),
+                "type": entry_type(entry.node),
+                "path": path.to_string_lossy(),
+            }));
+        }
+        flatten(&entry.children, &path, values);
+    }
+}
+
+/// Write the differences as a flat JSON array of changed paths
+pub fn write_json(entries: &[DiffEntry], out: &mut impl Write) -> Result<()> {
+    let mut values = Vec::new();
+    flatten(entries, Path::new(""), &mut values);
+    serde_json::to_writer_pretty(&mut *out, &values)?;
+    writeln!(out)?;
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::ffi::OsStr;
+
+// Entries hidden unless all files are requested
+const HIDDEN_NAMES: [&str; 1] = ["__pycache__"];
+
+/// Filter entries on their name, from the glob patterns given on the command line
+pub struct EntryFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    all: bool,
+}
+
+/// Build a glob set, where each pattern may hold several alternatives
+/// separated by `|` like in GNU tree
+fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.iter().flat_map(|p| p.split('|')) {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+impl EntryFilter {
+    pub fn new(include: &[String], exclude: &[String], all: bool) -> Result<Self> {
+        Ok(Self {
+            include: build_globset(include)?,
+            exclude: build_globset(exclude)?,
+            all,
+        })
+    }
+
+    pub fn is_hidden(name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        name.starts_with(".") || HIDDEN_NAMES.contains(&name.as_ref())
+    }
+
+    /// Whether the name matches the include patterns, if any
+    pub fn is_included(&self, name: &OsStr) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(name))
+    }
+
+    /// Include patterns only apply to files, while exclude patterns also
+    /// apply to directories
+    pub fn is_visible(&self, name: &OsStr, is_dir: bool) -> bool {
+        if !self.all && Self::is_hidden(name) {
+            return false;
+        }
+        if self.exclude.as_ref().is_some_and(|set| set.is_match(name)) {
+            return false;
+        }
+        is_dir || self.is_included(name)
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::Result;
+use colored::{ColoredString, Colorize};
+use git2::{Repository, Status, StatusOptions};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Change of an entry, ordered by importance when rolled up onto directories
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Change {
+    Ignored,
+    Untracked,
+    Typechange,
+    Renamed,
+    Deleted,
+    Modified,
+    New,
+}
+
+impl Change {
+    /// Letter of `git status --short`
+    fn letter(&self) -> &'static str {
+        match self {
+            Self::Ignored => "!",
+            Self::Untracked => "?",
+            Self::Typechange => "T",
+            Self::Renamed => "R",
+            Self::Deleted => "D",
+            Self::Modified => "M",
+            Self::New => "A",
+        }
+    }
+}
+
+/// Changes of an entry in the index (staged) and in the working tree
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitStatus {
+    index: Option<Change>,
+    worktree: Option<Change>,
+}
+
+impl GitStatus {
+    fn from_flags(status: Status) -> Self {
+        let index = if status.is_index_new() {
+            Some(Change::New)
+        } else if status.is_index_modified() {
+            Some(Change::Modified)
+        } else if status.is_index_deleted() {
+            Some(Change::Deleted)
+        } else if status.is_index_renamed() {
+            Some(Change::Renamed)
+        } else if status.is_index_typechange() {
+            Some(Change::Typechange)
+        } else {
+            None
+        };
+        let worktree = if status.is_wt_new() {
+            Some(Change::Untracked)
+        } else if status.is_wt_modified() {
+            Some(Change::Modified)
+        } else if status.is_wt_deleted() {
+            Some(Change::Deleted)
+        } else if status.is_wt_renamed() {
+            Some(Change::Renamed)
+        } else if status.is_wt_typechange() {
+            Some(Change::Typechange)
+        } else if status.is_ignored() {
+            Some(Change::Ignored)
+        } else {
+            None
+        };
+        Self { index, worktree }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.index = self.index.max(other.index);
+        self.worktree = self.worktree.max(other.worktree);
+    }
+
+    /// Two letters like `git status --short`, or "--" for unchanged entries
+    pub fn format(&self) -> String {
+        match (self.index, self.worktree) {
+            (None, Some(Change::Untracked)) => "??".magenta().to_string(),
+            (None, Some(Change::Ignored)) => "!!".dimmed().to_string(),
+            (index, worktree) => format!(
+                "{}{}",
+                colored_letter(index, ColoredString::green),
+                colored_letter(worktree, ColoredString::red)
+            ),
+        }
+    }
+}
+
+fn colored_letter(
+    change: Option<Change>,
+    color: fn(ColoredString) -> ColoredString,
+) -> ColoredString {
+    match change {
+        Some(change) => color(change.letter().normal()),
+        None => "-".dimmed(),
+    }
+}
+
+/// Status of the entries of the git repository holding the walked directory
+pub struct GitStatuses {
+    workdir: PathBuf,
+    /// Entries reported by git, and directories with the changes of their content
+    statuses: HashMap<PathBuf, GitStatus>,
+    /// Untracked and ignored directories, whose content shares their status
+    directories: HashMap<PathBuf, GitStatus>,
+}
+
+impl GitStatuses {
+    /// Read the index and working tree of the repository holding `root`,
+    /// if any
+    pub fn open(root: &Path) -> Result<Option<Self>> {
+        let Ok(repo) = Repository::discover(root) else {
+            return Ok(None);
+        };
+        let Some(workdir) = repo.workdir() else {
+            return Ok(None);
+        };
+        let mut result = Self {
+            workdir: workdir.canonicalize()?,
+            statuses: HashMap::new(),
+            directories: HashMap::new(),
+        };
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .include_ignored(true)
+            .renames_head_to_index(true);
+        for entry in repo.statuses(Some(&mut options))?.iter() {
+            let bytes = entry.path_bytes();
+            let path = PathBuf::from(OsStr::from_bytes(bytes.strip_suffix(b"/").unwrap_or(bytes)));
+            let status = GitStatus::from_flags(entry.status());
+            // libgit2 reports empty directories as ignored, unlike git
+            if status.worktree == Some(Change::Ignored) && !repo.is_path_ignored(&path)? {
+                continue;
+            }
+            if bytes.ends_with(b"/") {
+                result.directories.insert(path.clone(), status);
+            }
+            // Ignored entries do not make their parents ignored
+            let rolled_up = GitStatus {
+                index: status.index,
+                worktree: status.worktree.filter(|change| *change != Change::Ignored),
+            };
+            for parent in path.ancestors().skip(1) {
+                result
+                    .statuses
+                    .entry(parent.to_path_buf())
+                    .or_default()
+                    .merge(rolled_up);
+            }
+            result.statuses.entry(path).or_default().merge(status);
+        }
+        Ok(Some(result))
+    }
+
+    pub fn status(&self, path: &Path) -> GitStatus {
+        let Ok(relative) = path.strip_prefix(&self.workdir) else {
+            return GitStatus::default();
+        };
+        if let Some(status) = self.statuses.get(relative) {
+            return *status;
+        }
+        relative
+            .ancestors()
+            .skip(1)
+            .find_map(|parent| self.directories.get(parent))
+            .copied()
+            .unwrap_or_default()
+    }
+}
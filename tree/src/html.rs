@@ -0,0 +1,144 @@
+use crate::columns::Columns;
+use crate::json::node_error;
+use crate::theme::Theme;
+use crate::walk::Node;
+use crate::xml::escape;
+use anyhow::Result;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+const STYLE: &str = "
+body { font-family: monospace; margin: 2em; }
+ul { list-style: none; margin: 0; padding-left: 1.5em; border-left: 1px dotted #aaa; }
+ul.tree { padding-left: 0; border-left: none; }
+summary { cursor: pointer; }
+a { color: inherit; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.directory > a { color: #2a5db0; font-weight: bold; }
+.meta { color: #777; white-space: pre; }
+.error { color: #c00; }
+.report { margin-top: 1em; }
+";
+
+/// Render the tree as a self-contained HTML page, with collapsible
+/// directories linking to their location below a base URL
+pub struct HtmlRenderer {
+    base_url: String,
+    title: String,
+    columns: Columns,
+    theme: Theme,
+}
+
+/// Percent-encode a relative path, keeping the separators
+fn encode_path(path: &Path) -> String {
+    let mut result = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            result.push(byte as char);
+        } else {
+            result += &format!("%{:02X}", byte);
+        }
+    }
+    result
+}
+
+impl HtmlRenderer {
+    pub fn new(base_url: &str, title: &str, columns: Columns, theme: Theme) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            title: title.to_string(),
+            columns,
+            theme,
+        }
+    }
+
+    /// Icon, link and metadata of an entry
+    fn label(&self, node: &Node, name: &str, relative: &Path) -> String {
+        let mut href = format!("{}/{}", self.base_url, encode_path(relative));
+        if node.is_dir && !relative.as_os_str().is_empty() {
+            href.push('/');
+        }
+        let class = if node.is_dir { "directory" } else { "file" };
+        let mut result = format!("<span class=\"{}\">", class);
+        if !self.columns.is_empty() {
+            let meta = self.columns.format(&node.metadata, node.size);
+            result += &format!("<span class=\"meta\">{}</span>  ", escape(&meta));
+        }
+        // Extension icons are Nerd Font glyphs, which readers of the page
+        // would see as boxes without a patched font
+        if node.is_dir {
+            if let Some(icon) = self.theme.icon(node) {
+                result += &format!("{} ", icon);
+            }
+        }
+        result += &format!("<a href=\"{}\">{}</a>", escape(&href), escape(name));
+        if let Some(link) = &node.link {
+            result += &format!(" -&gt; {}", escape(&link.target.to_string_lossy()));
+        }
+        if let Some(err) = node_error(node) {
+            result += &format!("  <span class=\"error\">[{}]</span>", escape(err));
+        }
+        result += "</span>";
+        result
+    }
+
+    fn render_node(
+        &self,
+        node: &Node,
+        name: &str,
+        root: &Path,
+        indent: usize,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        let relative = node.path.strip_prefix(root).unwrap_or(&node.path);
+        let padding = "  ".repeat(indent);
+        let label = self.label(node, name, relative);
+        if node.children.is_empty() && node.omitted == 0 {
+            writeln!(out, "{}<li>{}</li>", padding, label)?;
+            return Ok(());
+        }
+        let open = if indent == 1 { " open" } else { "" };
+        writeln!(out, "{}<li><details{}>", padding, open)?;
+        writeln!(out, "{}  <summary>{}</summary>", padding, label)?;
+        writeln!(out, "{}  <ul>", padding)?;
+        for child in node.children.iter() {
+            self.render_node(child, &child.name, root, indent + 2, out)?;
+        }
+        if node.omitted > 0 {
+            writeln!(out, "{}    <li>... and {} more</li>", padding, node.omitted)?;
+        }
+        writeln!(out, "{}  </ul>", padding)?;
+        writeln!(out, "{}</details></li>", padding)?;
+        Ok(())
+    }
+
+    /// Render the tree, naming the root as it was given on the command line
+    /// and ending with the report unless it is empty
+    pub fn render(
+        &self,
+        root: &Node,
+        root_name: &str,
+        report: &str,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>")?;
+        writeln!(out, "<head>")?;
+        writeln!(out, "<meta charset=\"utf-8\">")?;
+        writeln!(out, "<title>{}</title>", escape(&self.title))?;
+        writeln!(out, "<style>{}</style>", STYLE)?;
+        writeln!(out, "</head>")?;
+        writeln!(out, "<body>")?;
+        writeln!(out, "<h1>{}</h1>", escape(&self.title))?;
+        writeln!(out, "<ul class=\"tree\">")?;
+        self.render_node(root, root_name, &root.path, 1, out)?;
+        writeln!(out, "</ul>")?;
+        if !report.is_empty() {
+            writeln!(out, "<p class=\"report\">{}</p>", escape(report))?;
+        }
+        writeln!(out, "</body>")?;
+        writeln!(out, "</html>")?;
+        Ok(())
+    }
+}
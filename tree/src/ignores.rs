@@ -0,0 +1,153 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Which kinds of ignore files should be honoured
+#[derive(Clone, Copy, Debug)]
+pub struct IgnoreOptions {
+    pub dot_ignore: bool,
+    pub vcs: bool,
+    pub global: bool,
+}
+
+/// Ignore files found in a single directory
+struct Layer {
+    parent: Option<Arc<Layer>>,
+    in_repo: bool,
+    dot_ignore: Option<Gitignore>,
+    gitignore: Option<Gitignore>,
+    exclude: Option<Gitignore>,
+}
+
+/// Ignore rules applying to the entries of a directory, built from the ignore
+/// files of the directory and of all its parents, like `rg` and `fd` do.
+#[derive(Clone)]
+pub struct IgnoreRules {
+    options: IgnoreOptions,
+    global: Option<Arc<Gitignore>>,
+    top: Option<Arc<Layer>>,
+}
+
+fn load(path: &Path) -> Option<Gitignore> {
+    if !path.is_file() {
+        return None;
+    }
+    let (gitignore, err) = Gitignore::new(path);
+    if let Some(err) = err {
+        eprintln!("Warning: {}", err);
+    }
+    Some(gitignore)
+}
+
+fn load_exclude(repo: &Path) -> Option<Gitignore> {
+    let path = repo.join(".git").join("info").join("exclude");
+    if !path.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(repo);
+    if let Some(err) = builder.add(&path) {
+        eprintln!("Warning: {}", err);
+    }
+    builder.build().ok()
+}
+
+impl IgnoreRules {
+    /// Rules for the entries of `root`, including ignore files of its parents
+    pub fn new(root: &Path, options: IgnoreOptions) -> Self {
+        let global = if options.vcs && options.global {
+            let (gitignore, err) = Gitignore::global();
+            if let Some(err) = err {
+                eprintln!("Warning: {}", err);
+            }
+            Some(Arc::new(gitignore))
+        } else {
+            None
+        };
+        let mut rules = Self {
+            options,
+            global,
+            top: None,
+        };
+        let mut ancestors = root.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+        for dir in ancestors {
+            rules = rules.descend(dir);
+        }
+        rules
+    }
+
+    fn enabled(&self) -> bool {
+        self.options.dot_ignore || self.options.vcs
+    }
+
+    /// Rules for the entries of `dir`, which must be an entry of the current directory
+    pub fn descend(&self, dir: &Path) -> Self {
+        if !self.enabled() {
+            return self.clone();
+        }
+        let parent_in_repo = self.top.as_ref().is_some_and(|layer| layer.in_repo);
+        let is_repo = dir.join(".git").exists();
+        let in_repo = parent_in_repo || is_repo;
+        let layer = Layer {
+            parent: self.top.clone(),
+            in_repo,
+            dot_ignore: self
+                .options
+                .dot_ignore
+                .then(|| load(&dir.join(".ignore")))
+                .flatten(),
+            gitignore: (self.options.vcs && in_repo)
+                .then(|| load(&dir.join(".gitignore")))
+                .flatten(),
+            exclude: (self.options.vcs && is_repo)
+                .then(|| load_exclude(dir))
+                .flatten(),
+        };
+        if layer.dot_ignore.is_none()
+            && layer.gitignore.is_none()
+            && layer.exclude.is_none()
+            && in_repo == parent_in_repo
+        {
+            return self.clone();
+        }
+        Self {
+            options: self.options,
+            global: self.global.clone(),
+            top: Some(Arc::new(layer)),
+        }
+    }
+
+    /// Check whether an entry of the current directory is ignored. `.ignore`
+    /// files take precedence over `.gitignore` files, which take precedence
+    /// over the repository excludes and the global excludes. Within each
+    /// kind, the deepest ignore file wins.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+        let kinds: [fn(&Layer) -> Option<&Gitignore>; 3] = [
+            |layer| layer.dot_ignore.as_ref(),
+            |layer| layer.gitignore.as_ref(),
+            |layer| layer.exclude.as_ref(),
+        ];
+        for kind in kinds {
+            let mut layer = self.top.as_deref();
+            while let Some(current) = layer {
+                if let Some(gitignore) = kind(current) {
+                    match gitignore.matched(path, is_dir) {
+                        Match::Ignore(_) => return true,
+                        Match::Whitelist(_) => return false,
+                        Match::None => {}
+                    }
+                }
+                layer = current.parent.as_deref();
+            }
+        }
+        let in_repo = self.top.as_ref().is_some_and(|layer| layer.in_repo);
+        match &self.global {
+            Some(global) if in_repo => global.matched(path, is_dir).is_ignore(),
+            _ => false,
+        }
+    }
+}
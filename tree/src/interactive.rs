@@ -0,0 +1,319 @@
+use crate::render::{display_name, Charset, Connectors};
+use crate::theme::Theme;
+use crate::walk::Node;
+use anyhow::{anyhow, Result};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::collections::HashSet;
+use std::env;
+use std::io::{self, Stderr};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The browser is drawn on stderr, so that the printed path can be captured
+type Backend = CrosstermBackend<Stderr>;
+
+const HELP: &str = "↑↓ move  ←→ fold  / filter  p print  e edit  q quit";
+
+/// A line of the browser, with the connectors drawn in front of the entry
+struct Row<'a> {
+    node: &'a Node,
+    prefix: String,
+}
+
+enum Action {
+    Quit,
+    Print(PathBuf),
+    Edit(PathBuf),
+}
+
+/// Browse a tree in the terminal with collapsible folders
+struct Browser<'a> {
+    root: &'a Node,
+    theme: Theme,
+    connectors: Connectors,
+    expanded: HashSet<PathBuf>,
+    query: String,
+    /// Whether the keys are typed into the query
+    typing: bool,
+    /// Entries matching the query and their parents, while filtering
+    matches: Option<HashSet<PathBuf>>,
+    /// Error of the last command, shown in the status line
+    message: Option<String>,
+    state: ListState,
+    page: usize,
+}
+
+/// Insert the entries below `node` whose name contains `query`, along with
+/// their parents. Return whether any entry matched.
+fn collect_matches(node: &Node, query: &str, matches: &mut HashSet<PathBuf>) -> bool {
+    let mut found = false;
+    for child in node.children.iter() {
+        let matched = child.name.to_lowercase().contains(query);
+        if collect_matches(child, query, matches) || matched {
+            matches.insert(child.path.clone());
+            found = true;
+        }
+    }
+    found
+}
+
+fn suspend() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stderr(), LeaveAlternateScreen)
+}
+
+fn resume() -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stderr(), EnterAlternateScreen)
+}
+
+/// Open a file with `$EDITOR`, which may contain arguments like "code -w"
+fn edit(path: &Path) -> Result<()> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program).args(words).arg(path).status()?;
+    if !status.success() {
+        return Err(anyhow!("{} exited with {}", program, status));
+    }
+    Ok(())
+}
+
+impl<'a> Browser<'a> {
+    fn new(root: &'a Node, theme: Theme, charset: Charset) -> Self {
+        Self {
+            root,
+            theme,
+            connectors: charset.connectors(),
+            expanded: HashSet::from([root.path.clone()]),
+            query: String::new(),
+            typing: false,
+            matches: None,
+            message: None,
+            state: ListState::default().with_selected(Some(0)),
+            page: 1,
+        }
+    }
+
+    fn is_shown(&self, node: &Node) -> bool {
+        self.matches
+            .as_ref()
+            .is_none_or(|matches| matches.contains(&node.path))
+    }
+
+    /// Directories are all unfolded while filtering
+    fn is_expanded(&self, node: &Node) -> bool {
+        self.matches.is_some() || self.expanded.contains(&node.path)
+    }
+
+    fn push_children(&self, node: &'a Node, prefix: &str, rows: &mut Vec<Row<'a>>) {
+        if !self.is_expanded(node) {
+            return;
+        }
+        let children = node
+            .children
+            .iter()
+            .filter(|child| self.is_shown(child))
+            .collect::<Vec<_>>();
+        for (i, child) in children.iter().enumerate() {
+            let is_last = i == children.len() - 1;
+            let (connector, indent) = if is_last {
+                (self.connectors.last, self.connectors.space)
+            } else {
+                (self.connectors.branch, self.connectors.vertical)
+            };
+            rows.push(Row {
+                node: child,
+                prefix: format!("{}{}", prefix, connector),
+            });
+            self.push_children(child, &format!("{}{}", prefix, indent), rows);
+        }
+    }
+
+    fn rows(&self) -> Vec<Row<'a>> {
+        let mut rows = vec![Row {
+            node: self.root,
+            prefix: String::new(),
+        }];
+        self.push_children(self.root, "", &mut rows);
+        rows
+    }
+
+    fn line(&self, row: &Row) -> Line<'static> {
+        let node = row.node;
+        let marker = if node.children.is_empty() {
+            "  "
+        } else if self.is_expanded(node) {
+            self.connectors.unfolded
+        } else {
+            self.connectors.folded
+        };
+        Line::from(vec![
+            Span::raw(format!("{}{}", row.prefix, marker)),
+            Span::styled(
+                display_name(node, &self.theme),
+                self.theme.style(node).to_ratatui(),
+            ),
+        ])
+    }
+
+    fn status(&self) -> Line<'static> {
+        if let Some(message) = &self.message {
+            return Line::from(message.clone().red());
+        }
+        if self.typing || !self.query.is_empty() {
+            let cursor = if self.typing { "█" } else { "" };
+            return Line::from(vec![
+                "/".bold(),
+                Span::raw(format!("{}{}", self.query, cursor)),
+            ]);
+        }
+        Line::from(HELP.dim())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = self.rows();
+        let [list_area, status_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let title = format!(" {} ", self.root.path.display());
+        let list = List::new(rows.iter().map(|row| self.line(row)))
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().reversed());
+        self.page = list_area.height.saturating_sub(2).max(1) as usize;
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+        frame.render_widget(Paragraph::new(self.status()), status_area);
+    }
+
+    fn toggle(&mut self, path: &Path) {
+        if !self.expanded.remove(path) {
+            self.expanded.insert(path.to_path_buf());
+        }
+    }
+
+    fn update_matches(&mut self) {
+        self.matches = if self.query.is_empty() {
+            None
+        } else {
+            let mut matches = HashSet::new();
+            collect_matches(self.root, &self.query.to_lowercase(), &mut matches);
+            Some(matches)
+        };
+        self.state.select(Some(0));
+    }
+
+    fn type_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(c) => self.query.push(c),
+            KeyCode::Backspace => {
+                self.query.pop();
+            }
+            KeyCode::Enter => self.typing = false,
+            KeyCode::Esc => {
+                self.typing = false;
+                self.query.clear();
+            }
+            _ => return,
+        }
+        self.update_matches();
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        if self.typing {
+            self.type_key(key.code);
+            return None;
+        }
+        let rows = self.rows();
+        let selected = self.state.selected().unwrap_or(0).min(rows.len() - 1);
+        let node = rows[selected].node;
+        let can_fold = !node.children.is_empty() && self.matches.is_none();
+        match key.code {
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::PageDown => self.state.select(Some(selected + self.page)),
+            KeyCode::PageUp => self.state.select(Some(selected.saturating_sub(self.page))),
+            KeyCode::Home | KeyCode::Char('g') => self.state.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.state.select(Some(rows.len() - 1)),
+            KeyCode::Right | KeyCode::Char('l') => {
+                if !self.is_expanded(node) {
+                    self.expanded.insert(node.path.clone());
+                } else if !node.children.is_empty() {
+                    self.state.select_next();
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                if can_fold && selected > 0 && self.is_expanded(node) {
+                    self.expanded.remove(&node.path);
+                } else if let Some(parent) = rows[..selected]
+                    .iter()
+                    .rposition(|row| Some(row.node.path.as_path()) == node.path.parent())
+                {
+                    self.state.select(Some(parent));
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(' ') if can_fold && selected > 0 => {
+                self.toggle(&node.path)
+            }
+            KeyCode::Char('/') => self.typing = true,
+            KeyCode::Char('p') => return Some(Action::Print(node.path.clone())),
+            KeyCode::Char('e') => return Some(Action::Edit(node.path.clone())),
+            KeyCode::Esc if !self.query.is_empty() => {
+                self.query.clear();
+                self.update_matches();
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Some(Action::Quit),
+            _ => {}
+        }
+        None
+    }
+
+    fn run(&mut self, terminal: &mut Terminal<Backend>) -> Result<Option<PathBuf>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            self.message = None;
+            match self.handle_key(key) {
+                Some(Action::Quit) => return Ok(None),
+                Some(Action::Print(path)) => return Ok(Some(path)),
+                Some(Action::Edit(path)) => {
+                    suspend()?;
+                    let result = edit(&path);
+                    resume()?;
+                    terminal.clear()?;
+                    if let Err(err) = result {
+                        self.message = Some(err.to_string());
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Browse the tree until the user quits, returning the path selected for
+/// printing if any
+pub fn browse(root: &Node, theme: Theme, charset: Charset) -> Result<Option<PathBuf>> {
+    resume()?;
+    let result = Terminal::new(CrosstermBackend::new(io::stderr()))
+        .map_err(anyhow::Error::from)
+        .and_then(|mut terminal| Browser::new(root, theme, charset).run(&mut terminal));
+    suspend()?;
+    result
+}
@@ -0,0 +1,88 @@
+use crate::columns::{date_string, permissions_string, Columns};
+use crate::walk::{LinkStatus, Node};
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+/// Render the tree as JSON, following the schema of GNU tree
+pub struct JsonRenderer {
+    columns: Columns,
+}
+
+/// Type of an entry, as named by GNU tree
+pub fn entry_type(node: &Node) -> &'static str {
+    if node.link.is_some() {
+        "link"
+    } else if node.is_dir {
+        "directory"
+    } else {
+        "file"
+    }
+}
+
+/// Error reported for an entry, either on its directory or on its link
+pub fn node_error(node: &Node) -> Option<&str> {
+    match node.link_status() {
+        Some(LinkStatus::Broken) => Some("broken link"),
+        Some(LinkStatus::Recursive) => Some("recursive, not followed"),
+        _ => node.error.as_deref(),
+    }
+}
+
+impl JsonRenderer {
+    pub fn new(columns: Columns) -> Self {
+        Self { columns }
+    }
+
+    fn node_value(&self, node: &Node, name: &str) -> Value {
+        let options = self.columns.options();
+        let mut map = Map::new();
+        map.insert("type".into(), json!(entry_type(node)));
+        map.insert("name".into(), json!(name));
+        if let Some(link) = &node.link {
+            map.insert("target".into(), json!(link.target.to_string_lossy()));
+        }
+        if options.permissions {
+            let mode = node.metadata.permissions().mode() & 0o7777;
+            map.insert("mode".into(), json!(format!("{:04o}", mode)));
+            map.insert("prot".into(), json!(permissions_string(&node.metadata)));
+        }
+        if options.owner {
+            map.insert(
+                "user".into(),
+                json!(self.columns.owner(node.metadata.uid())),
+            );
+        }
+        if options.size || options.human {
+            map.insert("size".into(), json!(node.size));
+        }
+        if options.date {
+            map.insert("time".into(), json!(date_string(&node.metadata)));
+        }
+        if let Some(err) = node_error(node) {
+            map.insert("error".into(), json!(err));
+        }
+        if node.is_real_dir() || !node.children.is_empty() {
+            let contents = node
+                .children
+                .iter()
+                .map(|child| self.node_value(child, &child.name))
+                .collect::<Vec<_>>();
+            map.insert("contents".into(), Value::Array(contents));
+        }
+        Value::Object(map)
+    }
+
+    /// Render the tree, naming the root as it was given on the command line
+    pub fn render(&self, root: &Node, root_name: &str, out: &mut impl Write) -> Result<()> {
+        let (directories, files) = root.count();
+        let value = json!([
+            self.node_value(root, root_name),
+            {"type": "report", "directories": directories, "files": files},
+        ]);
+        serde_json::to_writer_pretty(&mut *out, &value)?;
+        writeln!(out)?;
+        Ok(())
+    }
+}
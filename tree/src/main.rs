@@ -1,116 +1,290 @@
+mod columns;
+mod diff;
+mod filter;
+mod git;
+mod html;
+mod ignores;
+mod interactive;
+mod json;
+mod render;
+mod sort;
+mod stats;
+mod theme;
+mod walk;
+mod xml;
+
 use anyhow::{anyhow, Result};
-use clap::Parser;
-use colored::{ColoredString, Colorize};
+use clap::{ArgAction, Parser};
+use columns::{ColumnOptions, Columns};
+use diff::DiffRenderer;
+use filter::EntryFilter;
+use git::GitStatuses;
+use html::HtmlRenderer;
+use ignores::{IgnoreOptions, IgnoreRules};
+use json::JsonRenderer;
+use render::{Charset, TextRenderer};
+use sort::{SortKey, SortOptions};
 use std::env;
-use std::io;
-use std::path::Path;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use theme::Theme;
+use walk::{WalkOptions, Walker};
+use xml::XmlRenderer;
 
 #[derive(Parser, Debug)]
+#[command(disable_help_flag = true)]
 struct Arguments {
     #[arg(default_value = ".")]
     directory: String,
 
-    #[arg(long, short)]
+    #[arg(long, short, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
     max_depth: Option<usize>,
 
     #[arg(long, short, default_value_t = false)]
     directory_only: bool,
+
+    /// List only files matching the glob pattern (may be repeated)
+    #[arg(long, short = 'P')]
+    pattern: Vec<String>,
+
+    /// Do not list files and directories matching the glob pattern (may be repeated)
+    #[arg(long, short = 'I')]
+    ignore: Vec<String>,
+
+    /// Apply the -P patterns to directories too, listing the content of the
+    /// matching ones and only the paths leading to matches
+    #[arg(long, default_value_t = false, requires = "pattern")]
+    matchdirs: bool,
+
+    /// List at most N entries per directory, followed by the number of left out ones
+    #[arg(long, value_name = "N")]
+    filelimit: Option<usize>,
+
+    /// Show hidden files and directories
+    #[arg(long, short, default_value_t = false)]
+    all: bool,
+
+    /// Do not show directories left empty after filtering
+    #[arg(long, default_value_t = false)]
+    prune: bool,
+
+    /// Print the size of each file in bytes
+    #[arg(short, default_value_t = false)]
+    size: bool,
+
+    /// Print the size of each file in a human readable format
+    #[arg(short, default_value_t = false)]
+    human: bool,
+
+    /// Print the file type and permissions of each file
+    #[arg(short, default_value_t = false)]
+    permissions: bool,
+
+    /// Print the owner of each file
+    #[arg(short = 'u', default_value_t = false)]
+    owner: bool,
+
+    /// Print the date of last modification of each file
+    #[arg(short = 'D', default_value_t = false)]
+    date: bool,
+
+    /// Print the size of directories as the accumulation of their content
+    #[arg(long, default_value_t = false)]
+    du: bool,
+
+    /// Print the number of inodes below each directory, largest first, like the
+    /// file-count quota of the servers (hidden and git-ignored entries are counted)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["json", "xml", "stats", "interactive"])]
+    count: bool,
+
+    /// Entries left out by --count (may be repeated, replacing the defaults)
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        default_values = ["miniforge3", "anaconda3", ".ipython", ".cargo", ".rustup", ".local", ".cache", ".vscode*", "CMakeFiles", "*.pyc"]
+    )]
+    count_ignore: Vec<String>,
+
+    /// Descend into symbolic links to directories
+    #[arg(long, short = 'l', default_value_t = false)]
+    follow: bool,
+
+    /// Order of the entries within each directory
+    #[arg(long, value_enum, default_value_t = SortKey::Name)]
+    sort: SortKey,
+
+    /// Reverse the order of the entries
+    #[arg(long, short, default_value_t = false)]
+    reverse: bool,
+
+    /// List directories before files
+    #[arg(long, default_value_t = false)]
+    dirsfirst: bool,
+
+    /// Print the git status of each entry, directories showing the changes of their content
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["json", "xml", "html", "interactive", "count", "stats"]
+    )]
+    git: bool,
+
+    /// Print the entries added, removed or changed in another directory, which
+    /// are compared in full, without columns nor git status
+    #[arg(
+        long,
+        value_name = "OTHER",
+        conflicts_with_all = [
+            "xml", "html", "stats", "interactive", "count", "filelimit", "size", "human",
+            "permissions", "owner", "date", "git", "du",
+        ]
+    )]
+    diff: Option<String>,
+
+    /// Compare the content of files with --diff, instead of their modification time
+    #[arg(long, default_value_t = false, requires = "diff")]
+    checksum: bool,
+
+    /// Print the tree as JSON
+    #[arg(short = 'J', default_value_t = false, conflicts_with = "xml")]
+    json: bool,
+
+    /// Print the tree as XML
+    #[arg(short = 'X', default_value_t = false)]
+    xml: bool,
+
+    /// Browse the tree in the terminal, printing the path selected with 'p'
+    #[arg(long, default_value_t = false, conflicts_with_all = ["json", "xml", "stats"])]
+    interactive: bool,
+
+    /// Print the tree as an HTML page, linking the entries below the base URL
+    #[arg(
+        short = 'H',
+        value_name = "BASEURL",
+        conflicts_with_all = ["json", "xml", "stats", "interactive", "count"]
+    )]
+    html: Option<String>,
+
+    /// Title of the HTML page
+    #[arg(long, short = 'T', default_value = "Directory Tree")]
+    title: String,
+
+    /// Characters used to draw the tree
+    #[arg(long, value_enum, default_value_t = Charset::Utf8)]
+    charset: Charset,
+
+    /// Do not print the count of directories and files at the end
+    #[arg(long, default_value_t = false)]
+    noreport: bool,
+
+    /// Print the number of files and their size per extension instead of the tree
+    #[arg(long, default_value_t = false, conflicts_with_all = ["json", "xml"])]
+    stats: bool,
+
+    /// Do not print icons, for fonts without Nerd Font glyphs
+    #[arg(long, default_value_t = false)]
+    no_icons: bool,
+
+    /// Theme configuration file [default: ~/.config/tree/config.toml]
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Number of threads walking the directories [default: number of CPUs]
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Do not respect .ignore, .gitignore and global git excludes
+    #[arg(long, default_value_t = false)]
+    no_ignore: bool,
+
+    /// Do not respect .gitignore, .git/info/exclude and global git excludes
+    #[arg(long, default_value_t = false)]
+    no_ignore_vcs: bool,
+
+    /// Do not respect the global git excludes file
+    #[arg(long, default_value_t = false)]
+    no_ignore_global: bool,
+
+    /// Print help
+    #[arg(long, action = ArgAction::Help)]
+    help: Option<bool>,
 }
 
-fn display_path(path: &Path) -> Option<ColoredString> {
-    let name = path.file_name().unwrap().to_string_lossy();
-    if name.starts_with(".") || name == "__pycache__" {
-        return None;
+impl Arguments {
+    fn column_options(&self) -> ColumnOptions {
+        ColumnOptions {
+            size: self.size || self.du,
+            human: self.human,
+            permissions: self.permissions,
+            owner: self.owner,
+            date: self.date,
+        }
     }
-    let mut result = if path.is_dir() {
-        format!("📁 {}", name)
-    } else {
-        match path.extension() {
-            None => format!("{}", name),
-            Some(ext) => match ext.to_str().unwrap() {
-                "rs" => format!(" {}", name),
-                "go" => format!(" {}", name),
-                "py" => format!(" {}", name),
-                "npy" => format!(" {}", name),
-                "zig" => format!(" {}", name),
-                "jl" => format!(" {}", name),
-                "c" | "cpp" | "h" | "hpp" => format!(" {}", name),
-                "js" => format!(" {}", name),
-                "html" => format!(" {}", name),
-                "css" => format!(" {}", name),
-                "json" | "ajson" | "ameta" => format!(" {}", name),
-                "toml" => format!(" {}", name),
-                "zip" => format!(" {}", name),
-                "tar" => format!(" {}", name),
-                "gz" => format!(" {}", name),
-                "sh" => format!(" {}", name),
-                "csv" => format!(" {}", name),
-                "xlsx" => format!("󱎏 {}", name),
-                "pdf" => format!("󰈦 {}", name),
-                "msh" => format!("󰔷 {}", name),
-                _ => format!("{}", name),
-            },
+
+    /// Patterns of ignored entries, which include the ones of `--count`
+    fn ignore_patterns(&self) -> Vec<String> {
+        let mut patterns = self.ignore.clone();
+        if self.count {
+            patterns.extend(self.count_ignore.iter().cloned());
         }
-    };
-    if path.is_symlink() {
-        result = format!("{} -> {}", result, path.read_link().unwrap().display());
+        patterns
     }
-    if path.is_dir() {
-        Some(result.blue().bold())
-    } else {
-        Some(result.normal())
+
+    /// Only the top-level directories are reported by `--count` by default
+    fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            max_depth: if self.count {
+                Some(self.max_depth.unwrap_or(1))
+            } else {
+                self.max_depth
+            },
+            directory_only: self.directory_only,
+            prune: self.prune || self.matchdirs,
+            match_dirs: self.matchdirs,
+            file_limit: self.filelimit,
+            du: self.du,
+            count: self.count,
+            follow: self.follow,
+            sort: SortOptions {
+                key: self.sort,
+                reverse: self.reverse,
+                dirs_first: self.dirsfirst,
+            },
+        }
     }
-}
 
-fn print_tree(
-    path: &Path,
-    prefix: &str,
-    depth: usize,
-    max_depth: Option<usize>,
-    directory_only: bool,
-) -> Result<()> {
-    let mut children = path
-        .read_dir()?
-        .map(|res| res.map(|e| e.path()))
-        .collect::<Result<Vec<_>, io::Error>>()?;
-    if directory_only {
-        children.retain(|child| child.is_dir());
+    /// The plain tree is printed while the directories are walked, the other
+    /// outputs need the whole tree, like `--du` and `--prune` which need the
+    /// content of a directory before printing it
+    fn is_streamed(&self) -> bool {
+        !(self.json
+            || self.xml
+            || self.html.is_some()
+            || self.interactive
+            || self.count
+            || self.stats
+            || self.du
+            || self.prune
+            || self.matchdirs)
     }
-    children.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
-    for (i, child) in children.iter().enumerate() {
-        let root_char = if i == children.len() - 1 {
-            "└"
-        } else {
-            "├"
-        };
-        let child_name = if let Some(name) = display_path(child) {
-            name
-        } else {
-            continue;
-        };
-        println!("{}{}── {}", prefix, root_char, child_name);
-        if child.is_dir() && !child.is_symlink() {
-            if max_depth.is_some() && depth >= max_depth.unwrap() {
-                continue;
-            }
-            let new_prefix = if i == children.len() - 1 {
-                format!("{}    ", prefix)
-            } else {
-                format!("{}│   ", prefix)
-            };
-            print_tree(child, &new_prefix, depth + 1, max_depth, directory_only)?;
+
+    fn ignore_options(&self) -> IgnoreOptions {
+        let ignore = self.no_ignore || self.count;
+        IgnoreOptions {
+            dot_ignore: !ignore,
+            vcs: !ignore && !self.no_ignore_vcs,
+            global: !ignore && !self.no_ignore_global,
         }
     }
-    Ok(())
 }
 
-fn main() -> Result<()> {
-    let args = Arguments::parse();
+/// Canonical path of a directory given on the command line
+fn resolve(directory: &str) -> Result<PathBuf> {
     let cwd = env::current_dir()?;
-    let base_path = if args.directory == *"." {
+    let base_path = if directory == "." {
         Path::new(&cwd)
     } else {
-        Path::new(&args.directory)
+        Path::new(directory)
     };
     if !base_path.exists() {
         return Err(anyhow!("{} does not exist", base_path.display()));
@@ -118,7 +292,147 @@ fn main() -> Result<()> {
     if base_path.is_file() {
         return Err(anyhow!("{} is a file", base_path.display()));
     }
-    println!("{}", display_path(base_path).unwrap());
-    print_tree(base_path, "", 1, args.max_depth, args.directory_only)?;
+    Ok(base_path.canonicalize()?)
+}
+
+fn load_theme(args: &Arguments) -> Result<Theme> {
+    let mut theme = match &args.config {
+        Some(path) if !path.is_file() => {
+            return Err(anyhow!("{} does not exist", path.display()));
+        }
+        Some(path) => Theme::load(Some(path))?,
+        None => Theme::load(theme::default_config_path().as_deref())?,
+    };
+    if args.no_icons {
+        theme.disable_icons();
+    }
+    Ok(theme)
+}
+
+/// Statuses of the repository holding the root with `--git`
+fn open_git(args: &Arguments, root: &Path) -> Result<Option<GitStatuses>> {
+    if !args.git {
+        return Ok(None);
+    }
+    let statuses = GitStatuses::open(root)?;
+    if statuses.is_none() {
+        eprintln!(
+            "Warning: {} is not in a git repository, ignoring --git",
+            root.display()
+        );
+    }
+    Ok(statuses)
+}
+
+/// Walk both trees at the same time and print their differences
+fn diff_trees(
+    args: &Arguments,
+    walker: &Walker,
+    (root, rules): (&Path, &IgnoreRules),
+    other: &str,
+) -> Result<()> {
+    let other_root = resolve(other)?;
+    let other_rules = IgnoreRules::new(&other_root, args.ignore_options());
+    let (tree, other_tree) = rayon::join(
+        || walker.walk(root, rules),
+        || walker.walk(&other_root, &other_rules),
+    );
+    let (tree, other_tree) = (tree?, other_tree?);
+    let entries = diff::diff(&tree, &other_tree, args.checksum);
+    let mut out = BufWriter::new(io::stdout().lock());
+    if args.json {
+        diff::write_json(&entries, &mut out)?;
+    } else {
+        DiffRenderer::new(args.charset, load_theme(args)?).render(&tree, &entries, &mut out)?;
+        if !args.noreport {
+            writeln!(out)?;
+            writeln!(out, "{}", diff::report(&entries))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Arguments::parse();
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+    let root = resolve(&args.directory)?;
+    let filter = EntryFilter::new(
+        &args.pattern,
+        &args.ignore_patterns(),
+        args.all || args.count,
+    )?;
+    let rules = IgnoreRules::new(&root, args.ignore_options());
+    let walker = Walker::new(args.walk_options(), filter);
+    if let Some(other) = &args.diff {
+        return diff_trees(&args, &walker, (&root, &rules), other);
+    }
+    let columns = Columns::new(args.column_options());
+    let mut out = BufWriter::new(io::stdout().lock());
+    if args.is_streamed() {
+        let git = open_git(&args, &root)?;
+        let renderer = TextRenderer::new(args.charset, columns, load_theme(&args)?, git);
+        let counts = walker.stream(&root, &rules, |tree, listing| {
+            renderer.render_stream(tree, listing, &mut out)
+        })?;
+        if !args.noreport {
+            stats::write_report(counts, None, args.directory_only, args.human, &mut out)?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+    let tree = walker.walk(&root, &rules)?;
+    if args.json {
+        JsonRenderer::new(columns).render(&tree, &args.directory, &mut out)?;
+    } else if args.xml {
+        XmlRenderer::new(columns).render(&tree, &args.directory, &mut out)?;
+    } else {
+        let theme = load_theme(&args)?;
+        if let Some(base_url) = &args.html {
+            let report = if args.noreport {
+                String::new()
+            } else {
+                stats::report(
+                    tree.count(),
+                    args.du.then_some(tree.size),
+                    args.directory_only,
+                    args.human,
+                )
+            };
+            HtmlRenderer::new(base_url, &args.title, columns, theme).render(
+                &tree,
+                &args.directory,
+                &report,
+                &mut out,
+            )?;
+        } else if args.interactive {
+            if let Some(path) = interactive::browse(&tree, theme, args.charset)? {
+                writeln!(out, "{}", path.display())?;
+            }
+        } else if args.count {
+            stats::write_counts(&tree, &theme, &mut out)?;
+        } else {
+            if args.stats {
+                stats::write_stats(&tree, &theme, args.human, &mut out)?;
+            } else {
+                let git = open_git(&args, &root)?;
+                TextRenderer::new(args.charset, columns, theme, git).render(&tree, &mut out)?;
+            }
+            if !args.noreport {
+                stats::write_report(
+                    tree.count(),
+                    args.du.then_some(tree.size),
+                    args.directory_only,
+                    args.human,
+                    &mut out,
+                )?;
+            }
+        }
+    }
+    out.flush()?;
     Ok(())
 }
@@ -0,0 +1,208 @@
+use crate::columns::Columns;
+use crate::git::GitStatuses;
+use crate::theme::Theme;
+use crate::walk::{LinkStatus, Listing, Node};
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::ColoredString;
+use std::io::Write;
+use std::sync::mpsc::TryRecvError;
+
+/// Characters used to draw the branches of the tree
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Charset {
+    #[value(alias = "utf-8")]
+    Utf8,
+    Ascii,
+}
+
+pub struct Connectors {
+    pub branch: &'static str,
+    pub last: &'static str,
+    pub vertical: &'static str,
+    pub space: &'static str,
+    /// Markers of folded and unfolded directories in the interactive mode
+    pub folded: &'static str,
+    pub unfolded: &'static str,
+}
+
+impl Charset {
+    pub fn connectors(&self) -> Connectors {
+        match self {
+            Self::Utf8 => Connectors {
+                branch: "├── ",
+                last: "└── ",
+                vertical: "│   ",
+                space: "    ",
+                folded: "▸ ",
+                unfolded: "▾ ",
+            },
+            Self::Ascii => Connectors {
+                branch: "|-- ",
+                last: "`-- ",
+                vertical: "|   ",
+                space: "    ",
+                folded: "+ ",
+                unfolded: "- ",
+            },
+        }
+    }
+}
+
+/// Icon and name of an entry, followed by the target of symbolic links
+pub fn display_name(node: &Node, theme: &Theme) -> String {
+    let mut result = match theme.icon(node) {
+        Some(icon) => format!("{} {}", icon, node.name),
+        None => node.name.clone(),
+    };
+    if let Some(link) = &node.link {
+        result = format!("{} -> {}", result, link.target.display());
+    }
+    result
+}
+
+pub fn display_path(node: &Node, theme: &Theme) -> ColoredString {
+    theme.style(node).apply(&display_name(node, theme))
+}
+
+/// Render the tree as text, with box-drawing connectors
+pub struct TextRenderer {
+    connectors: Connectors,
+    columns: Columns,
+    theme: Theme,
+    git: Option<GitStatuses>,
+}
+
+impl TextRenderer {
+    pub fn new(charset: Charset, columns: Columns, theme: Theme, git: Option<GitStatuses>) -> Self {
+        Self {
+            connectors: charset.connectors(),
+            columns,
+            theme,
+            git,
+        }
+    }
+
+    /// Line of an entry, after the prefix drawing the branches
+    fn write_entry(&self, node: &Node, prefix: &str, out: &mut impl Write) -> Result<()> {
+        write!(out, "{}", prefix)?;
+        if !self.columns.is_empty() {
+            write!(out, "{}  ", self.columns.format(&node.metadata, node.size))?;
+        }
+        if let Some(git) = &self.git {
+            write!(out, "{} ", git.status(&node.path).format())?;
+        }
+        write!(out, "{}", display_path(node, &self.theme))?;
+        match node.link_status() {
+            Some(LinkStatus::Broken) => write!(out, "  [broken link]")?,
+            Some(LinkStatus::Recursive) => write!(out, "  [recursive, not followed]")?,
+            _ if node.error.is_some() => write!(out, "  [error opening dir]")?,
+            _ => {}
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    fn render_children(&self, node: &Node, prefix: &str, out: &mut impl Write) -> Result<()> {
+        for (i, child) in node.children.iter().enumerate() {
+            let is_last = i == node.children.len() - 1 && node.omitted == 0;
+            let connector = if is_last {
+                self.connectors.last
+            } else {
+                self.connectors.branch
+            };
+            self.write_entry(child, &format!("{}{}", prefix, connector), out)?;
+            let new_prefix = if is_last {
+                format!("{}{}", prefix, self.connectors.space)
+            } else {
+                format!("{}{}", prefix, self.connectors.vertical)
+            };
+            self.render_children(child, &new_prefix, out)?;
+        }
+        self.write_omitted(node.omitted, prefix, out)
+    }
+
+    fn write_omitted(&self, omitted: usize, prefix: &str, out: &mut impl Write) -> Result<()> {
+        if omitted > 0 {
+            writeln!(
+                out,
+                "{}{}... and {} more",
+                prefix, self.connectors.last, omitted
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Render the entries of a streamed directory, adding the directories
+    /// and files written to `counts`
+    fn render_listing(
+        &self,
+        listing: Listing,
+        prefix: &str,
+        counts: &mut (usize, usize),
+        out: &mut impl Write,
+    ) -> Result<()> {
+        let len = listing.entries.len();
+        for (i, entry) in listing.entries.into_iter().enumerate() {
+            let mut child = entry.node;
+            // The line of a directory tells whether it could be opened
+            let child_listing = match entry.listing {
+                Some(receiver) => Some(match receiver.try_recv() {
+                    Ok(listing) => listing,
+                    Err(TryRecvError::Empty) => {
+                        // Print what is ready while the directory is listed
+                        out.flush()?;
+                        receiver.recv()?
+                    }
+                    Err(err) => return Err(err.into()),
+                }),
+                None => None,
+            };
+            if let Some(child_listing) = &child_listing {
+                child.error = child_listing.error.clone();
+            }
+            let is_last = i == len - 1 && listing.omitted == 0;
+            let connector = if is_last {
+                self.connectors.last
+            } else {
+                self.connectors.branch
+            };
+            self.write_entry(&child, &format!("{}{}", prefix, connector), out)?;
+            if child.is_dir {
+                counts.0 += 1;
+            } else {
+                counts.1 += 1;
+            }
+            if let Some(child_listing) = child_listing {
+                let new_prefix = if is_last {
+                    format!("{}{}", prefix, self.connectors.space)
+                } else {
+                    format!("{}{}", prefix, self.connectors.vertical)
+                };
+                self.render_listing(child_listing, &new_prefix, counts, out)?;
+            }
+        }
+        counts.0 += listing.omitted_directories;
+        counts.1 += listing.omitted - listing.omitted_directories;
+        self.write_omitted(listing.omitted, prefix, out)
+    }
+
+    /// Render the tree while it is walked, returning the number of
+    /// directories and files written for the report
+    pub fn render_stream(
+        &self,
+        root: &Node,
+        listing: Listing,
+        out: &mut impl Write,
+    ) -> Result<(usize, usize)> {
+        writeln!(out, "{}", display_path(root, &self.theme))?;
+        let mut counts = (0, 0);
+        self.render_listing(listing, "", &mut counts, out)?;
+        Ok(counts)
+    }
+
+    pub fn render(&self, root: &Node, out: &mut impl Write) -> Result<()> {
+        writeln!(out, "{}", display_path(root, &self.theme))?;
+        self.render_children(root, "", out)
+    }
+}
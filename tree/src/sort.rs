@@ -0,0 +1,100 @@
+use crate::walk::Node;
+use clap::ValueEnum;
+use std::cmp::Ordering;
+
+/// Key used to order the entries of a directory
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SortKey {
+    /// Byte order of names
+    Name,
+    /// Largest first
+    Size,
+    /// Oldest first
+    Mtime,
+    /// Alphabetical order of extensions
+    Ext,
+    /// Natural order of numbers within names
+    Version,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SortOptions {
+    pub key: SortKey,
+    pub reverse: bool,
+    pub dirs_first: bool,
+}
+
+/// Compare names chunk by chunk, so that digits are compared by their value
+/// and "run_9" comes before "run_10"
+pub fn version_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.as_bytes();
+    let mut b = b.as_bytes();
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (a_num, a_rest) = a.split_at(a_len);
+                let (b_num, b_rest) = b.split_at(b_len);
+                let a_trimmed = trim_zeros(a_num);
+                let b_trimmed = trim_zeros(b_num);
+                let ordering = a_trimmed
+                    .len()
+                    .cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed))
+                    .then_with(|| a_len.cmp(&b_len));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = a_rest;
+                b = b_rest;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let start = digits.iter().take_while(|c| **c == b'0').count();
+    &digits[start..]
+}
+
+impl SortOptions {
+    fn compare_keys(&self, a: &Node, b: &Node) -> Ordering {
+        let by_name = || a.path.file_name().cmp(&b.path.file_name());
+        match self.key {
+            SortKey::Name => by_name(),
+            SortKey::Size => b.size.cmp(&a.size).then_with(by_name),
+            SortKey::Mtime => a
+                .metadata
+                .modified()
+                .ok()
+                .cmp(&b.metadata.modified().ok())
+                .then_with(by_name),
+            SortKey::Ext => a.extension().cmp(&b.extension()).then_with(by_name),
+            SortKey::Version => version_cmp(&a.name, &b.name),
+        }
+    }
+
+    /// Directories stay first when the order is reversed
+    pub fn compare(&self, a: &Node, b: &Node) -> Ordering {
+        if self.dirs_first && a.is_dir != b.is_dir {
+            return b.is_dir.cmp(&a.is_dir);
+        }
+        let ordering = self.compare_keys(a, b);
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
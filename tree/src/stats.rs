@@ -0,0 +1,136 @@
+use crate::columns::human_size;
+use crate::theme::Theme;
+use crate::walk::Node;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::Write;
+
+// Label of files without extension
+const NO_EXTENSION: &str = "(none)";
+
+#[derive(Default)]
+struct ExtensionStats {
+    files: usize,
+    size: u64,
+}
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, singular)
+    } else {
+        format!("{} {}", count, plural)
+    }
+}
+
+fn format_size(size: u64, human: bool) -> String {
+    if human {
+        human_size(size).trim_start().to_string()
+    } else {
+        size.to_string()
+    }
+}
+
+/// Final report of GNU tree, like "3 directories, 5 files", preceded by the
+/// total size with `--du`
+pub fn report(
+    (directories, files): (usize, usize),
+    du_size: Option<u64>,
+    directory_only: bool,
+    human: bool,
+) -> String {
+    let mut result = String::new();
+    if let Some(size) = du_size {
+        result += &format!("{} used in ", format_size(size, human));
+    }
+    result += &plural(directories, "directory", "directories");
+    if !directory_only {
+        result += &format!(", {}", plural(files, "file", "files"));
+    }
+    result
+}
+
+pub fn write_report(
+    counts: (usize, usize),
+    du_size: Option<u64>,
+    directory_only: bool,
+    human: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{}", report(counts, du_size, directory_only, human))?;
+    Ok(())
+}
+
+fn collect(node: &Node, stats: &mut HashMap<String, ExtensionStats>) {
+    for child in node.children.iter() {
+        if child.is_dir {
+            collect(child, stats);
+            continue;
+        }
+        let ext = child.extension().unwrap_or(NO_EXTENSION);
+        let entry = stats.entry(ext.to_string()).or_default();
+        entry.files += 1;
+        entry.size += child.size;
+    }
+}
+
+/// Write the number of files and their total size for each extension,
+/// largest first
+pub fn write_stats(root: &Node, theme: &Theme, human: bool, out: &mut impl Write) -> Result<()> {
+    let mut stats = HashMap::new();
+    collect(root, &mut stats);
+    let mut stats = stats.into_iter().collect::<Vec<_>>();
+    stats.sort_by(|(a_ext, a), (b_ext, b)| {
+        b.size
+            .cmp(&a.size)
+            .then(b.files.cmp(&a.files))
+            .then(a_ext.cmp(b_ext))
+    });
+    writeln!(out, "{:<20} {:>10} {:>14}", "Extension", "Files", "Size")?;
+    for (ext, entry) in stats.iter() {
+        let label = match theme.extension_icon(ext) {
+            Some(icon) => format!("{} {}", icon, ext),
+            None => ext.clone(),
+        };
+        writeln!(
+            out,
+            "{:<20} {:>10} {:>14}",
+            label,
+            entry.files,
+            format_size(entry.size, human)
+        )?;
+    }
+    Ok(())
+}
+
+fn collect_directories<'a>(node: &'a Node, directories: &mut Vec<&'a Node>) {
+    for child in node.children.iter().filter(|child| child.is_dir) {
+        directories.push(child);
+        collect_directories(child, directories);
+    }
+}
+
+/// Write the number of inodes below each listed directory, largest first,
+/// to find the subtree weighing the most on the file-count quota
+pub fn write_counts(root: &Node, theme: &Theme, out: &mut impl Write) -> Result<()> {
+    let mut directories = Vec::new();
+    collect_directories(root, &mut directories);
+    directories.sort_by(|a, b| b.inodes.cmp(&a.inodes).then(a.path.cmp(&b.path)));
+    writeln!(out, "{:>10}  Directory", "Inodes")?;
+    for node in directories {
+        let path = node.path.strip_prefix(&root.path).unwrap_or(&node.path);
+        let label = match theme.icon(node) {
+            Some(icon) => format!("{} {}", icon, path.display()),
+            None => path.display().to_string(),
+        };
+        writeln!(
+            out,
+            "{:>10}  {}",
+            node.inodes,
+            theme.style(node).apply(&label)
+        )?;
+    }
+    // The root itself is not counted
+    writeln!(out, "{:>10}  total", root.inodes - 1)?;
+    Ok(())
+}
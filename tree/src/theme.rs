@@ -0,0 +1,356 @@
+use crate::walk::{LinkStatus, Node};
+use anyhow::{anyhow, Result};
+use colored::{Color, ColoredString, Colorize};
+use lscolors::LsColors;
+use ratatui::style::Modifier;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DIRECTORY_ICON: &str = "📁";
+
+const DEFAULT_ICONS: [(&str, &str); 25] = [
+    ("rs", ""),
+    ("go", ""),
+    ("py", ""),
+    ("npy", ""),
+    ("zig", ""),
+    ("jl", ""),
+    ("c", ""),
+    ("cpp", ""),
+    ("h", ""),
+    ("hpp", ""),
+    ("js", ""),
+    ("html", ""),
+    ("css", ""),
+    ("json", ""),
+    ("ajson", ""),
+    ("ameta", ""),
+    ("toml", ""),
+    ("zip", ""),
+    ("tar", ""),
+    ("gz", ""),
+    ("sh", ""),
+    ("csv", ""),
+    ("xlsx", "󱎏"),
+    ("pdf", "󰈦"),
+    ("msh", "󰔷"),
+];
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct IconConfig {
+    enabled: Option<bool>,
+    directory: Option<String>,
+    extensions: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ColorConfig {
+    directory: Option<String>,
+    symlink: Option<String>,
+    broken: Option<String>,
+    extensions: HashMap<String, String>,
+}
+
+/// Content of the TOML configuration file
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeConfig {
+    icons: IconConfig,
+    colors: ColorConfig,
+}
+
+/// Colour and font attributes applied to a name
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Style {
+    foreground: Option<Color>,
+    background: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+    reversed: bool,
+}
+
+impl Style {
+    /// Parse a space separated list of colours and attributes, like
+    /// `"bold blue"` or `"white on_red"`
+    fn parse(spec: &str) -> Result<Self> {
+        let mut style = Self::default();
+        for word in spec.split_whitespace() {
+            match word {
+                "bold" => style.bold = true,
+                "dimmed" => style.dimmed = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                "reversed" => style.reversed = true,
+                _ => match word.strip_prefix("on_") {
+                    Some(color) => style.background = Some(parse_color(color)?),
+                    None => style.foreground = Some(parse_color(word)?),
+                },
+            }
+        }
+        Ok(style)
+    }
+
+    fn from_ls_colors(style: &lscolors::Style) -> Self {
+        Self {
+            foreground: style.foreground.map(convert_color),
+            background: style.background.map(convert_color),
+            bold: style.font_style.bold,
+            dimmed: style.font_style.dimmed,
+            italic: style.font_style.italic,
+            underline: style.font_style.underline,
+            reversed: style.font_style.reverse,
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> ColoredString {
+        let mut result = text.normal();
+        if let Some(color) = self.foreground {
+            result = result.color(color);
+        }
+        if let Some(color) = self.background {
+            result = result.on_color(color);
+        }
+        if self.bold {
+            result = result.bold();
+        }
+        if self.dimmed {
+            result = result.dimmed();
+        }
+        if self.italic {
+            result = result.italic();
+        }
+        if self.underline {
+            result = result.underline();
+        }
+        if self.reversed {
+            result = result.reversed();
+        }
+        result
+    }
+
+    /// Convert to the style of the interactive mode
+    pub fn to_ratatui(self) -> ratatui::style::Style {
+        let mut style = ratatui::style::Style::new();
+        if let Some(color) = self.foreground {
+            style = style.fg(ratatui_color(color));
+        }
+        if let Some(color) = self.background {
+            style = style.bg(ratatui_color(color));
+        }
+        let modifiers = [
+            (self.bold, Modifier::BOLD),
+            (self.dimmed, Modifier::DIM),
+            (self.italic, Modifier::ITALIC),
+            (self.underline, Modifier::UNDERLINED),
+            (self.reversed, Modifier::REVERSED),
+        ];
+        for (enabled, modifier) in modifiers {
+            if enabled {
+                style = style.add_modifier(modifier);
+            }
+        }
+        style
+    }
+}
+
+fn ratatui_color(color: Color) -> ratatui::style::Color {
+    use ratatui::style::Color as Tui;
+    match color {
+        Color::Black => Tui::Black,
+        Color::Red => Tui::Red,
+        Color::Green => Tui::Green,
+        Color::Yellow => Tui::Yellow,
+        Color::Blue => Tui::Blue,
+        Color::Magenta => Tui::Magenta,
+        Color::Cyan => Tui::Cyan,
+        Color::White => Tui::Gray,
+        Color::BrightBlack => Tui::DarkGray,
+        Color::BrightRed => Tui::LightRed,
+        Color::BrightGreen => Tui::LightGreen,
+        Color::BrightYellow => Tui::LightYellow,
+        Color::BrightBlue => Tui::LightBlue,
+        Color::BrightMagenta => Tui::LightMagenta,
+        Color::BrightCyan => Tui::LightCyan,
+        Color::BrightWhite => Tui::White,
+        Color::AnsiColor(code) => Tui::Indexed(code),
+        Color::TrueColor { r, g, b } => Tui::Rgb(r, g, b),
+    }
+}
+
+fn parse_color(name: &str) -> Result<Color> {
+    name.parse()
+        .map_err(|_| anyhow!("Unknown colour '{}' in theme", name))
+}
+
+fn convert_color(color: lscolors::Color) -> Color {
+    match color {
+        lscolors::Color::Black => Color::Black,
+        lscolors::Color::Red => Color::Red,
+        lscolors::Color::Green => Color::Green,
+        lscolors::Color::Yellow => Color::Yellow,
+        lscolors::Color::Blue => Color::Blue,
+        lscolors::Color::Magenta => Color::Magenta,
+        lscolors::Color::Cyan => Color::Cyan,
+        lscolors::Color::White => Color::White,
+        lscolors::Color::BrightBlack => Color::BrightBlack,
+        lscolors::Color::BrightRed => Color::BrightRed,
+        lscolors::Color::BrightGreen => Color::BrightGreen,
+        lscolors::Color::BrightYellow => Color::BrightYellow,
+        lscolors::Color::BrightBlue => Color::BrightBlue,
+        lscolors::Color::BrightMagenta => Color::BrightMagenta,
+        lscolors::Color::BrightCyan => Color::BrightCyan,
+        lscolors::Color::BrightWhite => Color::BrightWhite,
+        lscolors::Color::Fixed(code) => Color::AnsiColor(code),
+        lscolors::Color::RGB(r, g, b) => Color::TrueColor { r, g, b },
+    }
+}
+
+/// Default location of the configuration file
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => env::home_dir()?.join(".config"),
+    };
+    Some(config_dir.join("tree").join("config.toml"))
+}
+
+/// Icons and colours of the entries. Colours from the configuration file
+/// take precedence over `LS_COLORS`, which takes precedence over the defaults.
+pub struct Theme {
+    icons_enabled: bool,
+    directory_icon: String,
+    icons: HashMap<String, String>,
+    directory_style: Option<Style>,
+    symlink_style: Option<Style>,
+    broken_style: Option<Style>,
+    extension_styles: HashMap<String, Style>,
+    ls_colors: Option<LsColors>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            icons_enabled: true,
+            directory_icon: DIRECTORY_ICON.to_string(),
+            icons: DEFAULT_ICONS
+                .iter()
+                .map(|(ext, icon)| (ext.to_string(), icon.to_string()))
+                .collect(),
+            directory_style: None,
+            symlink_style: None,
+            broken_style: None,
+            extension_styles: HashMap::new(),
+            ls_colors: LsColors::from_env(),
+        }
+    }
+}
+
+impl Theme {
+    /// Load the theme from a configuration file, if it exists
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut theme = Self::default();
+        let Some(path) = path.filter(|path| path.is_file()) else {
+            return Ok(theme);
+        };
+        let content = fs::read_to_string(path)?;
+        let config: ThemeConfig =
+            toml::from_str(&content).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        if let Some(enabled) = config.icons.enabled {
+            theme.icons_enabled = enabled;
+        }
+        if let Some(icon) = config.icons.directory {
+            theme.directory_icon = icon;
+        }
+        theme.icons.extend(config.icons.extensions);
+        theme.directory_style = config
+            .colors
+            .directory
+            .as_deref()
+            .map(Style::parse)
+            .transpose()?;
+        theme.symlink_style = config
+            .colors
+            .symlink
+            .as_deref()
+            .map(Style::parse)
+            .transpose()?;
+        theme.broken_style = config
+            .colors
+            .broken
+            .as_deref()
+            .map(Style::parse)
+            .transpose()?;
+        for (ext, spec) in config.colors.extensions.iter() {
+            theme
+                .extension_styles
+                .insert(ext.clone(), Style::parse(spec)?);
+        }
+        Ok(theme)
+    }
+
+    pub fn disable_icons(&mut self) {
+        self.icons_enabled = false;
+    }
+
+    pub fn icon(&self, node: &Node) -> Option<&str> {
+        if !self.icons_enabled {
+            return None;
+        }
+        if node.is_dir {
+            return Some(&self.directory_icon);
+        }
+        self.extension_icon(node.extension()?)
+    }
+
+    pub fn extension_icon(&self, ext: &str) -> Option<&str> {
+        if !self.icons_enabled {
+            return None;
+        }
+        let icon = self.icons.get(ext)?;
+        (!icon.is_empty()).then_some(icon.as_str())
+    }
+
+    pub fn style(&self, node: &Node) -> Style {
+        let broken = matches!(
+            node.link_status(),
+            Some(LinkStatus::Broken | LinkStatus::Recursive)
+        );
+        let configured = if broken && self.broken_style.is_some() {
+            self.broken_style
+        } else if node.link.is_some() && self.symlink_style.is_some() {
+            self.symlink_style
+        } else if node.is_dir {
+            self.directory_style
+        } else {
+            node.extension()
+                .and_then(|ext| self.extension_styles.get(ext))
+                .copied()
+        };
+        if let Some(style) = configured {
+            return style;
+        }
+        let from_env = self.ls_colors.as_ref().and_then(|ls_colors| {
+            ls_colors.style_for_path_with_metadata(&node.path, Some(&node.metadata))
+        });
+        match from_env {
+            Some(style) => Style::from_ls_colors(style),
+            None if broken => Style {
+                foreground: Some(Color::Red),
+                ..Style::default()
+            },
+            None if node.is_dir => Style {
+                foreground: Some(Color::Blue),
+                bold: true,
+                ..Style::default()
+            },
+            None => Style::default(),
+        }
+    }
+}
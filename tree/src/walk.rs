@@ -0,0 +1,453 @@
+use crate::filter::EntryFilter;
+use crate::ignores::IgnoreRules;
+use crate::sort::SortOptions;
+use anyhow::{anyhow, Result};
+use rayon::prelude::*;
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Options controlling which entries end up in the tree
+#[derive(Clone, Copy, Debug)]
+pub struct WalkOptions {
+    pub max_depth: Option<usize>,
+    pub directory_only: bool,
+    pub prune: bool,
+    /// Apply the include patterns to directories, listing all the content of
+    /// the matching ones
+    pub match_dirs: bool,
+    /// Maximum number of entries listed per directory
+    pub file_limit: Option<usize>,
+    pub du: bool,
+    pub count: bool,
+    pub follow: bool,
+    pub sort: SortOptions,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkStatus {
+    Valid,
+    /// The target does not exist
+    Broken,
+    /// The target is one of the parents of the link, or the link resolves to itself
+    Recursive,
+}
+
+pub struct Link {
+    pub target: PathBuf,
+    pub status: LinkStatus,
+}
+
+/// An entry of the tree, with its children when it is a listed directory
+pub struct Node {
+    pub path: PathBuf,
+    pub name: String,
+    pub metadata: Metadata,
+    /// Whether the entry is a directory, following symbolic links
+    pub is_dir: bool,
+    /// Device and inode of the entry, following symbolic links
+    pub inode: (u64, u64),
+    pub link: Option<Link>,
+    /// Size of the entry, accumulated over its content with `--du`
+    pub size: u64,
+    /// Number of inodes of the entry, accumulated over its content with `--count`
+    pub inodes: u64,
+    /// Error raised while opening the directory
+    pub error: Option<String>,
+    pub children: Vec<Node>,
+    /// Number of entries left out of `children` by the file limit
+    pub omitted: usize,
+    /// Number of directories among the left out entries
+    pub omitted_directories: usize,
+}
+
+impl Node {
+    fn new(path: PathBuf) -> io::Result<Self> {
+        let metadata = path.symlink_metadata()?;
+        Self::with_metadata(path, metadata)
+    }
+
+    /// Build a node from the metadata of the entry itself, not following links
+    fn with_metadata(path: PathBuf, metadata: Metadata) -> io::Result<Self> {
+        let mut is_dir = metadata.is_dir();
+        let mut inode = (metadata.dev(), metadata.ino());
+        let link = if metadata.file_type().is_symlink() {
+            let status = match fs::metadata(&path) {
+                Ok(target) => {
+                    is_dir = target.is_dir();
+                    inode = (target.dev(), target.ino());
+                    LinkStatus::Valid
+                }
+                Err(err) if err.raw_os_error() == Some(libc::ELOOP) => LinkStatus::Recursive,
+                Err(_) => LinkStatus::Broken,
+            };
+            Some(Link {
+                target: fs::read_link(&path).unwrap_or_default(),
+                status,
+            })
+        } else {
+            None
+        };
+        let name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .to_string();
+        Ok(Self {
+            size: metadata.len(),
+            inodes: 1,
+            path,
+            name,
+            metadata,
+            is_dir,
+            inode,
+            link,
+            error: None,
+            children: Vec::new(),
+            omitted: 0,
+            omitted_directories: 0,
+        })
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        Path::new(&self.name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+    }
+
+    /// Directories that can be descended into, excluding symbolic links
+    pub fn is_real_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+
+    pub fn link_status(&self) -> Option<LinkStatus> {
+        self.link.as_ref().map(|link| link.status)
+    }
+
+    /// Count the directories and files below this node, including the
+    /// entries left out by the file limit
+    pub fn count(&self) -> (usize, usize) {
+        let mut directories = self.omitted_directories;
+        let mut files = self.omitted - self.omitted_directories;
+        for child in self.children.iter() {
+            let (d, f) = child.count();
+            directories += d;
+            files += f;
+            if child.is_dir {
+                directories += 1;
+            } else {
+                files += 1;
+            }
+        }
+        (directories, files)
+    }
+}
+
+/// An entry streamed to the renderer, with the listing of its content
+/// when it is a directory the walker descends into
+pub struct StreamEntry {
+    pub node: Node,
+    pub listing: Option<Receiver<Listing>>,
+}
+
+/// The sorted entries of a directory, sent to the renderer once listed.
+/// Each subdirectory is listed in the background and sent through its own
+/// channel, so that the renderer receives the entries in order while the
+/// walk goes on ahead of it.
+pub struct Listing {
+    pub entries: Vec<StreamEntry>,
+    /// Number of entries left out by the file limit
+    pub omitted: usize,
+    /// Number of directories among the left out entries
+    pub omitted_directories: usize,
+    /// Error raised while opening the directory
+    pub error: Option<String>,
+}
+
+/// A directory waiting to be listed
+struct Pending {
+    path: PathBuf,
+    depth: usize,
+    rules: IgnoreRules,
+    /// Inodes of the directory and its parents, to detect cycles
+    ancestors: Vec<(u64, u64)>,
+    matched: bool,
+    sender: Sender<Listing>,
+}
+
+/// Walk a directory, either building its whole tree in memory, or streaming
+/// the entries to the renderer as the directories are listed
+pub struct Walker {
+    options: WalkOptions,
+    filter: EntryFilter,
+}
+
+impl Walker {
+    pub fn new(options: WalkOptions, filter: EntryFilter) -> Self {
+        Self { options, filter }
+    }
+
+    /// Entries below a matching directory are kept whatever their name
+    fn is_kept(&self, path: &Path, is_dir: bool, rules: &IgnoreRules, matched: bool) -> bool {
+        (is_dir || !self.options.directory_only)
+            && self
+                .filter
+                .is_visible(path.file_name().unwrap(), is_dir || matched)
+            && !rules.is_ignored(path, is_dir)
+    }
+
+    fn is_matching_dir(&self, node: &Node) -> bool {
+        self.options.match_dirs && node.is_dir && self.filter.is_included(node.name.as_ref())
+    }
+
+    /// List the entries of a directory. The file type returned by `read_dir`
+    /// tells directories apart without a stat call, so that only the entries
+    /// which are kept are stat'ed, except symbolic links that need to be
+    /// followed to be filtered.
+    fn list_children(
+        &self,
+        path: &Path,
+        rules: &IgnoreRules,
+        matched: bool,
+    ) -> io::Result<Vec<Node>> {
+        let mut children = Vec::new();
+        for entry in path.read_dir()? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let child = if file_type.is_symlink() {
+                Node::new(entry.path()).map(|child| {
+                    self.is_kept(&child.path, child.is_dir, rules, matched)
+                        .then_some(child)
+                })
+            } else {
+                let path = entry.path();
+                if !self.is_kept(&path, file_type.is_dir(), rules, matched) {
+                    continue;
+                }
+                entry
+                    .metadata()
+                    .and_then(|metadata| Node::with_metadata(path, metadata))
+                    .map(Some)
+            };
+            match child {
+                Ok(Some(child)) => children.push(child),
+                Ok(None) => {}
+                // The entry vanished in between
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(children)
+    }
+
+    fn is_listed(&self, depth: usize) -> bool {
+        self.options
+            .max_depth
+            .is_none_or(|max_depth| depth < max_depth)
+    }
+
+    /// Whether the walker descends into an entry, which is the case of
+    /// symbolic links to directories only when following them
+    fn descends(&self, node: &Node) -> bool {
+        match node.link_status() {
+            None => node.is_real_dir(),
+            Some(status) => self.options.follow && node.is_dir && status == LinkStatus::Valid,
+        }
+    }
+
+    /// Fill the children of a directory located at `depth` below the root,
+    /// `rules` being the ignore rules applying to its entries. `ancestors`
+    /// holds the inodes of the parent directories, to detect cycles, and
+    /// `matched` tells whether one of them matched with `--matchdirs`.
+    /// Subdirectories are walked in parallel, which pays off on network
+    /// filesystems where each call has a high latency.
+    fn fill(
+        &self,
+        node: &mut Node,
+        depth: usize,
+        rules: &IgnoreRules,
+        ancestors: &[(u64, u64)],
+        matched: bool,
+    ) {
+        let listed = self.is_listed(depth);
+        if !self.descends(node) || !(listed || self.options.du || self.options.count) {
+            return;
+        }
+        let matched = matched || (depth > 0 && self.is_matching_dir(node));
+        let mut children = match self.list_children(&node.path, rules, matched) {
+            Ok(children) => children,
+            Err(err) => {
+                node.error = Some(err.to_string());
+                return;
+            }
+        };
+        let mut ancestors = ancestors.to_vec();
+        ancestors.push(node.inode);
+        children
+            .par_iter_mut()
+            .filter(|child| self.descends(child))
+            .for_each(|child| {
+                if ancestors.contains(&child.inode) {
+                    if let Some(link) = child.link.as_mut() {
+                        link.status = LinkStatus::Recursive;
+                    }
+                    return;
+                }
+                let rules = rules.descend(&child.path);
+                self.fill(child, depth + 1, &rules, &ancestors, matched);
+            });
+        if self.options.prune && listed && !self.options.directory_only {
+            let child_listed = self.is_listed(depth + 1);
+            children.retain(|child| {
+                // Directories which are not descended into, like links which
+                // are not followed, are leaves filtered like files
+                if !self.descends(child) {
+                    return !child.is_dir
+                        || matched
+                        || self.filter.is_included(child.name.as_ref());
+                }
+                !child_listed
+                    || matched
+                    || self.is_matching_dir(child)
+                    || child.error.is_some()
+                    || !child.children.is_empty()
+            });
+        }
+        if self.options.du {
+            node.size += children.iter().map(|child| child.size).sum::<u64>();
+        }
+        if self.options.count {
+            node.inodes += children.iter().map(|child| child.inodes).sum::<u64>();
+        }
+        if listed {
+            (node.omitted, node.omitted_directories) = self.arrange(&mut children);
+            node.children = children;
+        }
+    }
+
+    /// Sort and truncate the entries of a listed directory, returning the
+    /// number of left out entries and directories among them
+    fn arrange(&self, children: &mut Vec<Node>) -> (usize, usize) {
+        children.sort_by(|a, b| self.options.sort.compare(a, b));
+        let Some(limit) = self.options.file_limit else {
+            return (0, 0);
+        };
+        let omitted = children.get(limit..).unwrap_or_default();
+        let directories = omitted.iter().filter(|child| child.is_dir).count();
+        let result = (omitted.len(), directories);
+        children.truncate(limit);
+        result
+    }
+
+    /// List a directory in the background. Its subdirectories are only
+    /// listed once the listing was handed over, so that the walk stops when
+    /// the renderer does.
+    fn spawn_listing<'s>(&'s self, scope: &rayon::Scope<'s>, dir: Pending) {
+        scope.spawn(move |scope| {
+            let mut pending = Vec::new();
+            let listing = match self.list_children(&dir.path, &dir.rules, dir.matched) {
+                Ok(mut children) => {
+                    let (omitted, omitted_directories) = self.arrange(&mut children);
+                    let entries = children
+                        .into_iter()
+                        .map(|mut child| {
+                            if !self.descends(&child) || !self.is_listed(dir.depth + 1) {
+                                return StreamEntry {
+                                    node: child,
+                                    listing: None,
+                                };
+                            }
+                            if dir.ancestors.contains(&child.inode) {
+                                if let Some(link) = child.link.as_mut() {
+                                    link.status = LinkStatus::Recursive;
+                                }
+                                return StreamEntry {
+                                    node: child,
+                                    listing: None,
+                                };
+                            }
+                            let (sender, receiver) = mpsc::channel();
+                            let mut ancestors = dir.ancestors.clone();
+                            ancestors.push(child.inode);
+                            pending.push(Pending {
+                                path: child.path.clone(),
+                                depth: dir.depth + 1,
+                                rules: dir.rules.descend(&child.path),
+                                ancestors,
+                                matched: dir.matched || self.is_matching_dir(&child),
+                                sender,
+                            });
+                            StreamEntry {
+                                node: child,
+                                listing: Some(receiver),
+                            }
+                        })
+                        .collect();
+                    Listing {
+                        entries,
+                        omitted,
+                        omitted_directories,
+                        error: None,
+                    }
+                }
+                Err(err) => Listing {
+                    entries: Vec::new(),
+                    omitted: 0,
+                    omitted_directories: 0,
+                    error: Some(err.to_string()),
+                },
+            };
+            if dir.sender.send(listing).is_ok() {
+                for child in pending {
+                    self.spawn_listing(scope, child);
+                }
+            }
+        });
+    }
+
+    /// Walk the tree below `root` in parallel, handing the listing of the
+    /// root to `render` as soon as it is read, whose result is returned.
+    /// Only the plain listing can be streamed, the options needing the
+    /// content of a directory before printing it require the whole tree.
+    pub fn stream<T>(
+        &self,
+        root: &Path,
+        rules: &IgnoreRules,
+        render: impl FnOnce(&Node, Listing) -> Result<T>,
+    ) -> Result<T> {
+        let node = Node::new(root.to_path_buf())?;
+        let (sender, receiver) = mpsc::channel();
+        // The renderer runs on the current thread, which is not one of the
+        // walking threads, so that waiting for a listing never blocks the walk
+        rayon::in_place_scope(|scope| {
+            self.spawn_listing(
+                scope,
+                Pending {
+                    path: node.path.clone(),
+                    depth: 0,
+                    rules: rules.clone(),
+                    ancestors: vec![node.inode],
+                    matched: false,
+                    sender,
+                },
+            );
+            let listing = receiver.recv()?;
+            if let Some(err) = &listing.error {
+                return Err(anyhow!("{}: {}", root.display(), err));
+            }
+            render(&node, listing)
+        })
+    }
+
+    /// Walk the tree below `root`, `rules` being the ignore rules applying to its entries
+    pub fn walk(&self, root: &Path, rules: &IgnoreRules) -> Result<Node> {
+        let mut node = Node::new(root.to_path_buf())?;
+        self.fill(&mut node, 0, rules, &[], false);
+        match node.error.take() {
+            Some(err) => Err(anyhow!("{}: {}", root.display(), err)),
+            None => Ok(node),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::columns::{date_string, permissions_string, Columns};
+use crate::json::{entry_type, node_error};
+use crate::walk::Node;
+use anyhow::Result;
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+/// Render the tree as XML, following the schema of GNU tree
+pub struct XmlRenderer {
+    columns: Columns,
+}
+
+pub fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&apos;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+impl XmlRenderer {
+    pub fn new(columns: Columns) -> Self {
+        Self { columns }
+    }
+
+    fn attributes(&self, node: &Node, name: &str) -> String {
+        let options = self.columns.options();
+        let mut result = format!(" name=\"{}\"", escape(name));
+        if let Some(link) = &node.link {
+            result += &format!(" target=\"{}\"", escape(&link.target.to_string_lossy()));
+        }
+        if options.permissions {
+            let mode = node.metadata.permissions().mode() & 0o7777;
+            result += &format!(" mode=\"{:04o}\"", mode);
+            result += &format!(" prot=\"{}\"", permissions_string(&node.metadata));
+        }
+        if options.owner {
+            let owner = self.columns.owner(node.metadata.uid());
+            result += &format!(" user=\"{}\"", escape(&owner));
+        }
+        if options.size || options.human {
+            result += &format!(" size=\"{}\"", node.size);
+        }
+        if options.date {
+            result += &format!(" time=\"{}\"", escape(&date_string(&node.metadata)));
+        }
+        result
+    }
+
+    fn render_node(
+        &self,
+        node: &Node,
+        name: &str,
+        indent: usize,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        let tag = entry_type(node);
+        let padding = "  ".repeat(indent);
+        write!(out, "{}<{}{}>", padding, tag, self.attributes(node, name))?;
+        let error = node_error(node);
+        if node.children.is_empty() && error.is_none() {
+            writeln!(out, "</{}>", tag)?;
+            return Ok(());
+        }
+        writeln!(out)?;
+        if let Some(err) = error {
+            writeln!(out, "{}  <error>{}</error>", padding, escape(err))?;
+        }
+        for child in node.children.iter() {
+            self.render_node(child, &child.name, indent + 1, out)?;
+        }
+        writeln!(out, "{}</{}>", padding, tag)?;
+        Ok(())
+    }
+
+    /// Render the tree, naming the root as it was given on the command line
+    pub fn render(&self, root: &Node, root_name: &str, out: &mut impl Write) -> Result<()> {
+        let (directories, files) = root.count();
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(out, "<tree>")?;
+        self.render_node(root, root_name, 1, out)?;
+        writeln!(out, "  <report>")?;
+        writeln!(out, "    <directories>{}</directories>", directories)?;
+        writeln!(out, "    <files>{}</files>", files)?;
+        writeln!(out, "  </report>")?;
+        writeln!(out, "</tree>")?;
+        Ok(())
+    }
+}